@@ -0,0 +1,141 @@
+//compares spawning and despawning projectiles every frame against hiding and reusing them
+//through the game's own pool::EntityPool
+//run with `cargo bench --bench entity_churn`
+
+use bevy::{
+    ecs::{schedule::Stage, system::EntityCommands},
+    prelude::*,
+};
+use game_thing::pool::{self, EntityPool, Poolable};
+use std::time::{Duration, Instant};
+
+//projectiles fired per frame and how many frames each one lives
+const PER_FRAME: usize = 64;
+const LIFETIME: u32 = 90;
+const FRAMES: u32 = 2000;
+
+#[derive(Component)]
+struct Projectile {
+    age: u32,
+    velocity: Vec2,
+}
+
+impl Poolable for Projectile {
+    const NAME: &'static str = "Projectile";
+
+    fn strip(entity: &mut EntityCommands) {
+        entity.remove::<Projectile>();
+    }
+}
+
+#[derive(Default)]
+struct Churn {
+    spawned: u64,
+    despawned: u64,
+}
+
+fn bundle(i: usize) -> SpriteSheetBundle {
+    SpriteSheetBundle {
+        transform: Transform::from_xyz(i as f32, 0.0, 0.0),
+        ..Default::default()
+    }
+}
+
+fn fire_fresh(mut commands: Commands, mut churn: ResMut<Churn>) {
+    for i in 0..PER_FRAME {
+        commands.spawn_bundle(bundle(i)).insert(Projectile {
+            age: 0,
+            velocity: Vec2::new(1.0, 0.5),
+        });
+        churn.spawned += 1;
+    }
+}
+
+fn expire_fresh(
+    mut commands: Commands,
+    mut churn: ResMut<Churn>,
+    mut q: Query<(Entity, &mut Projectile, &mut Transform)>,
+) {
+    for (ent, mut p, mut tr) in q.iter_mut() {
+        p.age += 1;
+        tr.translation += p.velocity.extend(0.0);
+        if p.age >= LIFETIME {
+            commands.entity(ent).despawn();
+            churn.despawned += 1;
+        }
+    }
+}
+
+fn fire_pooled(mut commands: Commands, mut pool: ResMut<EntityPool<Projectile>>) {
+    for i in 0..PER_FRAME {
+        pool.acquire(&mut commands)
+            .insert_bundle(bundle(i))
+            .insert(Projectile {
+                age: 0,
+                velocity: Vec2::new(1.0, 0.5),
+            });
+    }
+}
+
+fn expire_pooled(
+    mut commands: Commands,
+    mut pool: ResMut<EntityPool<Projectile>>,
+    mut q: Query<(Entity, &mut Projectile, &mut Transform)>,
+) {
+    for (ent, mut p, mut tr) in q.iter_mut() {
+        p.age += 1;
+        tr.translation += p.velocity.extend(0.0);
+        if p.age >= LIFETIME {
+            pool.release(&mut commands, ent);
+        }
+    }
+}
+
+fn run(name: &str, mut stage: SystemStage) {
+    let mut world = World::new();
+    world.insert_resource(Churn::default());
+    world.insert_resource(EntityPool::<Projectile>::default());
+
+    let start = Instant::now();
+    let mut worst = Duration::ZERO;
+    for _ in 0..FRAMES {
+        let frame = Instant::now();
+        stage.run(&mut world);
+        worst = worst.max(frame.elapsed());
+    }
+    let total = start.elapsed();
+
+    let churn = world.get_resource::<Churn>().unwrap();
+    let pool = world.get_resource::<EntityPool<Projectile>>().unwrap();
+    println!(
+        "{:<8} {:>8.2} us/frame  worst {:>8.2} us  {:>7} spawned  {:>7} despawned  {:>7} reused  {:>5} entities at the end",
+        name,
+        total.as_secs_f64() * 1e6 / FRAMES as f64,
+        worst.as_secs_f64() * 1e6,
+        churn.spawned + pool.misses,
+        churn.despawned,
+        pool.hits,
+        world.entities().len(),
+    );
+}
+
+fn main() {
+    println!(
+        "{} projectiles a frame living {} frames, {} frames",
+        PER_FRAME, LIFETIME, FRAMES
+    );
+    run(
+        "fresh",
+        SystemStage::single_threaded()
+            .with_system(fire_fresh.label("fire"))
+            .with_system(expire_fresh.after("fire")),
+    );
+    //released entities only go back out a frame later, once their removals are in, same as in the game
+    run(
+        "pooled",
+        SystemStage::single_threaded()
+            .with_system(pool::recycle::<Projectile>.label("recycle"))
+            .with_system(fire_pooled.label("fire").after("recycle"))
+            .with_system(expire_pooled.after("fire")),
+    );
+}
@@ -0,0 +1,257 @@
+use bevy::prelude::*;
+use log::warn;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayMode {
+    Loop,
+    //stops on the last frame and sends AnimationFinished
+    Once,
+    //runs to the end and back again forever
+    PingPong,
+}
+
+//a named run of atlas frames
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub frames: Vec<usize>,
+    pub fps: f32,
+    pub mode: PlayMode,
+}
+
+impl Clip {
+    pub fn new(frames: &[usize], fps: f32, mode: PlayMode) -> Clip {
+        Clip {
+            frames: frames.to_vec(),
+            fps,
+            mode,
+        }
+    }
+
+    //a clip that just holds one frame
+    pub fn still(frame: usize) -> Clip {
+        Clip::new(&[frame], 1.0, PlayMode::Loop)
+    }
+}
+
+//--components--//
+
+//drives the TextureAtlasSprite index of its entity from whichever clip is playing
+#[derive(Component, Clone, Debug)]
+pub struct Animation {
+    clips: HashMap<&'static str, Clip>,
+    current: &'static str,
+    //position within the clip's frames, not the atlas index
+    step: usize,
+    elapsed: f32,
+    reverse: bool,
+    finished: bool,
+}
+
+impl Animation {
+    pub fn new(start: &'static str, clips: Vec<(&'static str, Clip)>) -> Animation {
+        Animation {
+            clips: clips.into_iter().collect(),
+            current: start,
+            step: 0,
+            elapsed: 0.0,
+            reverse: false,
+            finished: false,
+        }
+    }
+
+    //switch clips from the start, playing the one already going does nothing
+    pub fn play(&mut self, clip: &'static str) {
+        if clip == self.current {
+            return;
+        }
+        if !self.clips.contains_key(clip) {
+            warn!("No animation clip called {}", clip);
+            return;
+        }
+        self.current = clip;
+        self.step = 0;
+        self.elapsed = 0.0;
+        self.reverse = false;
+        self.finished = false;
+    }
+
+    pub fn current(&self) -> &'static str {
+        self.current
+    }
+
+    //the atlas index to show right now
+    pub fn frame(&self) -> usize {
+        self.clips
+            .get(self.current)
+            .and_then(|clip| clip.frames.get(self.step))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    //move on by delta seconds, true the moment a Once clip runs out
+    fn advance(&mut self, delta: f32) -> bool {
+        let clip = match self.clips.get(self.current) {
+            Some(c) => c,
+            None => return false,
+        };
+        if self.finished
+            || clip.fps <= 0.0
+            || (clip.frames.len() < 2 && clip.mode != PlayMode::Once)
+        {
+            return false;
+        }
+
+        self.elapsed += delta;
+        let frame_time = 1.0 / clip.fps;
+        let last = clip.frames.len() - 1;
+        while self.elapsed >= frame_time {
+            self.elapsed -= frame_time;
+            match clip.mode {
+                PlayMode::Loop => self.step = (self.step + 1) % clip.frames.len(),
+                PlayMode::Once => {
+                    self.step += 1;
+                    if self.step >= last {
+                        self.step = last;
+                        self.finished = true;
+                        return true;
+                    }
+                }
+                PlayMode::PingPong => {
+                    if self.step == last {
+                        self.reverse = true;
+                    } else if self.step == 0 {
+                        self.reverse = false;
+                    }
+                    if self.reverse {
+                        self.step -= 1;
+                    } else {
+                        self.step += 1;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+//--events--//
+
+//a Once clip reached its last frame
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub clip: &'static str,
+}
+
+//--clips--//
+//every animated thing in the game, frames are indices into its atlas
+
+pub fn player() -> Animation {
+    Animation::new("idle", vec![("idle", Clip::still(0))])
+}
+
+pub fn enemy() -> Animation {
+    Animation::new("walk", vec![("walk", Clip::still(0))])
+}
+
+//pulses back and forth rather than jumping from the last frame to the first
+pub fn spawner() -> Animation {
+    Animation::new(
+        "idle",
+        vec![("idle", Clip::new(&[0, 1, 2], 8.0, PlayMode::PingPong))],
+    )
+}
+
+pub fn fireball() -> Animation {
+    Animation::new("fly", vec![("fly", Clip::still(0))])
+}
+
+//a HUD heart, frame 0 is full and 1 empty, it blinks out when the heart is lost
+pub fn heart(full: bool) -> Animation {
+    Animation::new(
+        if full { "full" } else { "empty" },
+        vec![
+            ("full", Clip::still(0)),
+            ("empty", Clip::still(1)),
+            ("break", Clip::new(&[0, 1, 0, 1], 10.0, PlayMode::Once)),
+        ],
+    )
+}
+
+//--systems--//
+
+//hearts are UI images rather than sprites, ui::show_heart_frames shows their frame
+pub fn animate(
+    time: Res<Time>,
+    mut finished: EventWriter<AnimationFinished>,
+    mut q: Query<(Entity, &mut Animation, Option<&mut TextureAtlasSprite>)>,
+) {
+    for (ent, mut animation, sprite) in q.iter_mut() {
+        if animation.advance(time.delta_seconds()) {
+            finished.send(AnimationFinished {
+                entity: ent,
+                clip: animation.current(),
+            });
+        }
+        let frame = animation.frame();
+        if let Some(mut sprite) = sprite {
+            if sprite.index != frame {
+                sprite.index = frame;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //the frames shown after each of steps frame times
+    fn frames(mode: PlayMode, steps: usize) -> Vec<usize> {
+        let mut animation =
+            Animation::new("clip", vec![("clip", Clip::new(&[4, 5, 6], 4.0, mode))]);
+        (0..steps)
+            .map(|_| {
+                animation.advance(0.25);
+                animation.frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        assert_eq!(frames(PlayMode::Once, 5), vec![5, 6, 6, 6, 6]);
+
+        //and says so the one time it gets there
+        let mut animation = Animation::new(
+            "clip",
+            vec![("clip", Clip::new(&[4, 5, 6], 4.0, PlayMode::Once))],
+        );
+        let ends: Vec<bool> = (0..4).map(|_| animation.advance(0.25)).collect();
+        assert_eq!(ends, vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn ping_pong_turns_at_both_ends() {
+        assert_eq!(frames(PlayMode::PingPong, 8), vec![5, 6, 5, 4, 5, 6, 5, 4]);
+    }
+
+    #[test]
+    fn loop_wraps() {
+        assert_eq!(frames(PlayMode::Loop, 5), vec![5, 6, 4, 5, 6]);
+    }
+
+    #[test]
+    fn play_starts_the_clip_over() {
+        let mut animation = heart(true);
+        animation.play("break");
+        assert_eq!(animation.frame(), 0);
+        for _ in 0..2 {
+            assert!(!animation.advance(0.1));
+        }
+        assert!(animation.advance(0.1));
+        assert_eq!(animation.frame(), 1);
+        animation.play("full");
+        assert_eq!(animation.frame(), 0);
+    }
+}
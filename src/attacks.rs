@@ -1,10 +1,7 @@
 use bevy::math::{Vec2, Vec3};
-use bevy::prelude::{Color, Commands, Handle, Image, Sprite, SpriteBundle, Transform};
-use log::{debug, error, info};
+use bevy::prelude::{Assets, Commands, Handle, TextureAtlas};
 
-use crate::Collider;
-use crate::Fireball;
-use crate::Hitbox;
+use crate::patterns::BulletPattern;
 
 // pub fn default(
 //     commands: &mut Commands,
@@ -26,100 +23,24 @@ use crate::Hitbox;
 //         .with(Collider::Projectile);
 // }
 
-fn fireball_size() -> Vec2 {
+pub fn fireball_size() -> Vec2 {
     Vec2::new(26.0, 26.0)
 }
 
-pub struct Split;
-impl Attack for Split {
-    fn attack(
-        self: &Self,
-        commands: &mut Commands,
-        origin: &Vec3,
-        target: &Vec3,
-        fire_sp: &Handle<Image>,
-    ) {
-        let up: Vec3 = Vec3::Y * 100.0;
-        let raw_target = *target - *origin;
-        let angle = up.angle_between(raw_target) + 90f32.to_radians();
-
-        let diff: Vec3 =
-            Vec3::new(angle.cos() + angle.sin(), angle.sin() - angle.cos(), 0.0).normalize() * 10.0;
-
-        let target_1 = Vec3::new(target.x + diff.x, target.y + diff.y, 0.0);
-        let target_2 = Vec3::new(target.x - diff.x, target.y - diff.y, 0.0);
-
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: fire_sp.clone(),
-                transform: Transform::from_translation(*origin),
-                ..Default::default()
-            })
-            .insert(Fireball {
-                origin: *origin,
-                target: target_1,
-            })
-            .insert(Collider::Projectile)
-            .insert(Hitbox(fireball_size()));
-
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: fire_sp.clone(),
-                transform: Transform::from_translation(*origin),
-                ..Default::default()
-            })
-            .insert(Fireball {
-                origin: *origin,
-                target: target_2,
-            })
-            .insert(Collider::Projectile)
-            .insert(Hitbox(fireball_size()));
-
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: fire_sp.clone(),
-                transform: Transform::from_translation(*origin),
-                ..Default::default()
-            })
-            .insert(Fireball {
-                origin: *origin,
-                target: *target,
-            })
-            .insert(Collider::Projectile)
-            .insert(Hitbox(fireball_size()));
-    }
-}
-pub struct Basic;
-
-impl Attack for Basic {
-    fn attack(
-        self: &Self,
-        commands: &mut Commands,
-        origin: &Vec3,
-        target: &Vec3,
-        fire_sp: &Handle<Image>,
-    ) {
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: fire_sp.clone(),
-                transform: Transform::from_translation(*origin),
-                ..Default::default()
-            })
-            .insert(Fireball {
-                origin: *origin,
-                target: *target,
-            })
-            .insert(Collider::Projectile)
-            .insert(Hitbox(fireball_size()));
-    }
-}
-
+//firing patterns themselves live in assets/patterns, see the patterns module
 pub trait Attack {
     fn attack(
-        self: &Self,
+        &self,
         commands: &mut Commands,
         origin: &Vec3,
         target: &Vec3,
-        fire_sp: &Handle<Image>,
+        fire_sp: &Handle<TextureAtlas>,
+        owner: usize,
     );
+
+    //identifies the attack in save files
+    fn name(&self) -> &str;
+
+    //image for the HUD, under assets, none until the pattern it fires is loaded
+    fn icon<'a>(&self, patterns: &'a Assets<BulletPattern>) -> Option<&'a str>;
 }
@@ -0,0 +1,372 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadState, LoadedAsset},
+    audio::AudioSource,
+    prelude::*,
+    reflect::TypeUuid,
+};
+use log::{error, info, warn};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use serde::Deserialize;
+use std::{collections::HashMap, io::Cursor};
+
+use crate::{settings::Settings, sfx::SoundEvent, Enemy, GameState, Wave};
+
+//a music track and how far it has faded in
+struct Deck {
+    sink: Sink,
+    fade: f32,
+}
+
+//--resources--//
+
+//plays through rodio directly, bevy's Audio can't change the volume of something already playing
+pub struct Mixer {
+    _stream: Option<OutputStream>,
+    handle: Option<OutputStreamHandle>,
+    music: Option<Deck>,
+    //tracks fading out after being replaced
+    outgoing: Vec<Deck>,
+    //sound effects still playing, kept to count voices
+    voices: HashMap<SoundEvent, Vec<Sink>>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        match OutputStream::try_default() {
+            Ok((stream, handle)) => Mixer {
+                _stream: Some(stream),
+                handle: Some(handle),
+                ..Mixer::silent()
+            },
+            Err(e) => {
+                warn!("No audio device, playing silently: {}", e);
+                Mixer::silent()
+            }
+        }
+    }
+}
+
+impl Mixer {
+    //for runs without a window, which shouldn't make any noise either
+    pub fn silent() -> Mixer {
+        Mixer {
+            _stream: None,
+            handle: None,
+            music: None,
+            outgoing: Vec::new(),
+            voices: HashMap::new(),
+        }
+    }
+
+    //start a track from silence, whatever was playing fades out under it
+    fn start_track(&mut self, source: &AudioSource) {
+        let handle = match self.handle {
+            Some(ref h) => h,
+            None => return,
+        };
+        let decoder = match Decoder::new(Cursor::new(source.clone())) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("Unable to decode music: {}", e);
+                return;
+            }
+        };
+        match Sink::try_new(handle) {
+            Ok(sink) => {
+                sink.set_volume(0.0);
+                sink.append(decoder);
+                let old = self.music.replace(Deck { sink, fade: 0.0 });
+                self.outgoing.extend(old);
+            }
+            Err(e) => error!("Unable to play music: {}", e),
+        }
+    }
+
+    //move every track a step along its fade, step is the fraction of a whole crossfade
+    fn fade_music(&mut self, step: f32, volume: f32) {
+        if let Some(ref mut deck) = self.music {
+            deck.fade = (deck.fade + step).min(1.0);
+            deck.sink.set_volume(deck.fade * volume);
+        }
+        for deck in self.outgoing.iter_mut() {
+            deck.fade = (deck.fade - step).max(0.0);
+            deck.sink.set_volume(deck.fade * volume);
+        }
+        self.outgoing
+            .retain(|deck| deck.fade > 0.0 && !deck.sink.empty());
+    }
+
+    //fade out whatever is playing without anything replacing it
+    fn stop_music(&mut self) {
+        self.outgoing.extend(self.music.take());
+    }
+
+    fn track_finished(&self) -> bool {
+        self.music.as_ref().is_some_and(|deck| deck.sink.empty())
+    }
+
+    fn pause_music(&self, paused: bool) {
+        for deck in self.music.iter().chain(self.outgoing.iter()) {
+            if paused {
+                deck.sink.pause();
+            } else {
+                deck.sink.play();
+            }
+        }
+    }
+
+    //play a sound effect once, unless max_voices copies of it are already going
+    pub fn play_sfx(
+        &mut self,
+        event: SoundEvent,
+        source: &AudioSource,
+        volume: f32,
+        max_voices: usize,
+    ) -> bool {
+        let handle = match self.handle {
+            Some(ref h) => h,
+            None => return false,
+        };
+        let voices = self.voices.entry(event).or_default();
+        voices.retain(|sink| !sink.empty());
+        if voices.len() >= max_voices {
+            return false;
+        }
+        let decoder = match Decoder::new(Cursor::new(source.clone())) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("Unable to decode {:?} sound: {}", event, e);
+                return false;
+            }
+        };
+        match Sink::try_new(handle) {
+            Ok(sink) => {
+                sink.set_volume(volume);
+                sink.append(decoder);
+                voices.push(sink);
+                true
+            }
+            Err(e) => {
+                error!("Unable to play {:?} sound: {}", event, e);
+                false
+            }
+        }
+    }
+}
+
+//how busy the game is, each level has its own playlist
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Intensity {
+    Calm,
+    Combat,
+    Boss,
+}
+
+//playlists and when to switch between them, authored as a .music.ron file in assets/audio
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "0e7f4b2a-91c3-4d6e-b8a5-3c2d1f6e9a74"]
+#[serde(default)]
+pub struct MusicConfig {
+    //seconds for one playlist to fade into the next
+    pub crossfade: f32,
+    //seconds to stay on a playlist before switching again, so it doesn't flap
+    pub hold: f32,
+    //live enemies it takes to go from calm to combat
+    pub combat_enemies: usize,
+    //from this wave on it's boss music
+    pub boss_wave: u32,
+    pub playlists: HashMap<Intensity, Vec<String>>,
+}
+
+impl Default for MusicConfig {
+    fn default() -> Self {
+        MusicConfig {
+            crossfade: 2.0,
+            hold: 8.0,
+            combat_enemies: 8,
+            boss_wave: 5,
+            playlists: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MusicConfigLoader;
+
+impl AssetLoader for MusicConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let config = ron::de::from_bytes::<MusicConfig>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["music.ron"]
+    }
+}
+
+//picks the playlist from what's going on and keeps its tracks coming
+pub struct MusicDirector {
+    config: Handle<MusicConfig>,
+    playlists: HashMap<Intensity, Vec<Handle<AudioSource>>>,
+    //what the game calls for right now
+    target: Intensity,
+    //what's actually playing
+    playing: Intensity,
+    track: usize,
+    //seconds since the playlist last changed
+    held: f32,
+    //set when the next track should start
+    advance: bool,
+}
+
+impl MusicDirector {
+    pub fn new(config: Handle<MusicConfig>) -> MusicDirector {
+        MusicDirector {
+            config,
+            playlists: HashMap::new(),
+            target: Intensity::Calm,
+            playing: Intensity::Calm,
+            track: 0,
+            held: 0.0,
+            advance: true,
+        }
+    }
+}
+
+//--systems--//
+
+//start loading the playlists once the config is in, and again whenever it's edited
+pub fn load_playlists(
+    mut director: ResMut<MusicDirector>,
+    mut events: EventReader<AssetEvent<MusicConfig>>,
+    configs: Res<Assets<MusicConfig>>,
+    asset_server: Res<AssetServer>,
+) {
+    for ev in events.iter() {
+        let handle = match ev {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != director.config {
+            continue;
+        }
+        if let Some(config) = configs.get(handle) {
+            let playlists = config
+                .playlists
+                .iter()
+                .map(|(intensity, tracks)| {
+                    let handles = tracks
+                        .iter()
+                        .map(|path| asset_server.load(path.as_str()))
+                        .collect();
+                    (*intensity, handles)
+                })
+                .collect();
+            director.playlists = playlists;
+            director.track = 0;
+            director.advance = true;
+            info!("Loaded {} playlists", config.playlists.len());
+        }
+    }
+}
+
+//calm in the menus, otherwise from how many enemies are up and how far the run has got
+pub fn pick_intensity(
+    mut director: ResMut<MusicDirector>,
+    configs: Res<Assets<MusicConfig>>,
+    state: Res<State<GameState>>,
+    wave: Option<Res<Wave>>,
+    enemies: Query<(), With<Enemy>>,
+) {
+    let config = match configs.get(&director.config) {
+        Some(c) => c,
+        None => return,
+    };
+    let in_run = !matches!(state.current(), GameState::Loading | GameState::MainMenu)
+        && !state.inactives().contains(&GameState::MainMenu);
+    director.target = match wave {
+        Some(wave) if in_run && wave.0 >= config.boss_wave => Intensity::Boss,
+        _ if in_run && enemies.iter().count() >= config.combat_enemies => Intensity::Combat,
+        _ => Intensity::Calm,
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn direct_music(
+    mut mixer: NonSendMut<Mixer>,
+    mut director: ResMut<MusicDirector>,
+    configs: Res<Assets<MusicConfig>>,
+    sources: Res<Assets<AudioSource>>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    state: Res<State<GameState>>,
+    time: Res<Time>,
+) {
+    //music holds wherever it is while the pause menu, or options over it, is up
+    let paused =
+        *state.current() == GameState::Paused || state.inactives().contains(&GameState::Paused);
+    mixer.pause_music(paused);
+    if paused {
+        return;
+    }
+
+    //nothing to do until the playlists have been read
+    let config = match configs.get(&director.config) {
+        Some(c) if !director.playlists.is_empty() => c,
+        _ => return,
+    };
+    director.held += time.delta_seconds();
+    if director.target != director.playing && director.held >= config.hold {
+        info!(
+            "Music going from {:?} to {:?}",
+            director.playing, director.target
+        );
+        director.playing = director.target;
+        director.track = 0;
+        director.held = 0.0;
+        director.advance = true;
+    } else if mixer.track_finished() && !director.advance {
+        director.track += 1;
+        director.advance = true;
+    }
+
+    if director.advance {
+        let tracks = director
+            .playlists
+            .get(&director.playing)
+            .cloned()
+            .unwrap_or_default();
+        //skip over tracks that failed to load, wait on ones that are still loading
+        let next = (0..tracks.len())
+            .map(|i| (director.track + i) % tracks.len())
+            .find(|i| asset_server.get_load_state(&tracks[*i]) != LoadState::Failed);
+        match next {
+            Some(i) => {
+                if let Some(source) = sources.get(&tracks[i]) {
+                    mixer.start_track(source);
+                    director.track = i;
+                    director.advance = false;
+                }
+            }
+            None => {
+                warn!("Nothing to play for {:?} music", director.playing);
+                mixer.stop_music();
+                director.advance = false;
+            }
+        }
+    }
+
+    let step = if config.crossfade > 0.0 {
+        time.delta_seconds() / config.crossfade
+    } else {
+        1.0
+    };
+    mixer.fade_music(step, settings.music_volume());
+}
@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use log::info;
+
+use crate::{
+    camera::ScreenShake,
+    config::GameConfig,
+    gameplay::{Enemy, Player, PlayerInputs, MAX_PLAYERS},
+    hits::{Despawning, HitOutcomes, Hostile},
+    particles::{Effect, ParticleEvent},
+    sfx::SoundEvent,
+    ui,
+};
+
+//how much a bomb shakes the screen, see camera::ScreenShake
+const BOMB_TRAUMA: f32 = 0.8;
+const ICON_SIZE: f32 = 12.0;
+const ICON_FULL: Color = Color::rgb(1.0, 0.45, 0.2);
+const ICON_EMPTY: Color = Color::rgba(0.35, 0.35, 0.45, 0.6);
+
+//--components--//
+
+//one slot in the HUD's row of bombs
+#[derive(Component)]
+pub struct BombIcon(pub u32);
+
+//--resources--//
+
+//bombs carried between all the players, any of them can set one off
+pub struct Bombs(pub u32);
+
+//--systems--//
+
+//set off a bomb around whichever player's key went down, holding it only uses one
+#[allow(clippy::too_many_arguments)]
+pub fn use_bomb(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    inputs: Res<PlayerInputs>,
+    mut held: Local<[bool; MAX_PLAYERS]>,
+    mut bombs: ResMut<Bombs>,
+    mut shake: ResMut<ScreenShake>,
+    players: Query<(&Player, &Transform)>,
+    mut enemies: Query<(Entity, &Transform, &mut Enemy), Without<Despawning>>,
+    hostile: Query<Entity, (With<Hostile>, Without<Despawning>)>,
+    mut outcomes: HitOutcomes,
+) {
+    for (player, player_tr) in players.iter() {
+        let bomb = inputs.get(player.slot).bomb;
+        let was_held = match held.get_mut(player.slot) {
+            Some(h) => std::mem::replace(h, bomb),
+            None => continue,
+        };
+        if !bomb || was_held || bombs.0 == 0 {
+            continue;
+        }
+        let center = player_tr.translation;
+        bombs.0 -= 1;
+
+        for (ent, tr, mut enemy) in enemies.iter_mut() {
+            let away = (tr.translation - center).truncate();
+            if enemy.health <= 0.0 || away.length() > config.bomb_radius {
+                continue;
+            }
+            outcomes.hurt(
+                &mut commands,
+                ent,
+                &mut enemy,
+                tr.translation,
+                config.bomb_damage,
+                away,
+                Some(player.slot),
+            );
+        }
+        for ent in hostile.iter() {
+            commands.entity(ent).insert(Despawning);
+        }
+
+        shake.add(BOMB_TRAUMA);
+        outcomes.sounds.send(SoundEvent::Bomb);
+        outcomes.effects.send(
+            ParticleEvent::new(Effect::Shockwave, center, Vec2::X).reaching(config.bomb_radius),
+        );
+        info!(
+            "Player {} set off a bomb, {} left",
+            player.slot + 1,
+            bombs.0
+        );
+    }
+}
+
+//light up one icon per bomb carried
+pub fn show_bombs(bombs: Res<Bombs>, mut q: Query<(&BombIcon, &mut UiColor)>) {
+    if !bombs.is_changed() {
+        return;
+    }
+    for (icon, mut color) in q.iter_mut() {
+        color.0 = if icon.0 < bombs.0 {
+            ICON_FULL
+        } else {
+            ICON_EMPTY
+        };
+    }
+}
+
+pub fn spawn_icons(panel: &mut ChildBuilder, count: u32) {
+    panel.spawn_bundle(ui::row()).with_children(|row| {
+        for i in 0..count {
+            row.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(ICON_SIZE), Val::Px(ICON_SIZE)),
+                    margin: Rect {
+                        left: Val::Px(3.0),
+                        right: Val::Px(3.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                color: ICON_EMPTY.into(),
+                ..Default::default()
+            })
+            .insert(BombIcon(i));
+        }
+    });
+}
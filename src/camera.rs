@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{config::GameConfig, gameplay::Player, CosmeticRng, MainCamera};
+
+//furthest the camera is thrown off at full trauma, in pixels
+const MAX_OFFSET: f32 = 16.0;
+//trauma lost per second
+const RECOVERY: f32 = 1.5;
+//room kept around each player when the camera pulls out to fit them
+const FRAME_MARGIN: f32 = 80.0;
+//fraction of the way to where it should be the camera goes each second
+const FRAME_SPEED: f32 = 4.0;
+
+//--resources--//
+
+//where the camera looks and how far it's pulled out, shaking goes on top
+pub struct Framing {
+    center: Vec2,
+    scale: f32,
+}
+
+impl Default for Framing {
+    fn default() -> Self {
+        Framing {
+            center: Vec2::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+//builds up from big hits and wears off, the camera shakes by its square so small bumps stay small
+#[derive(Default)]
+pub struct ScreenShake {
+    trauma: f32,
+}
+
+impl ScreenShake {
+    pub fn add(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+}
+
+//--systems--//
+
+//keep the whole arena and every player in view, pulling out when someone wanders off the edge
+pub fn frame_players(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut framing: ResMut<Framing>,
+    players: Query<&Transform, With<Player>>,
+) {
+    let half_size = config.half_size();
+    let (mut min, mut max) = (-half_size, half_size);
+    for tr in players.iter() {
+        let at = tr.translation.truncate();
+        min = min.min(at - Vec2::splat(FRAME_MARGIN));
+        max = max.max(at + Vec2::splat(FRAME_MARGIN));
+    }
+
+    let t = (FRAME_SPEED * time.delta_seconds()).min(1.0);
+    let scale = ((max - min) / (half_size * 2.0)).max_element();
+    framing.center = framing.center.lerp((min + max) / 2.0, t);
+    framing.scale += (scale - framing.scale) * t;
+}
+
+//the camera sits wherever it's framed, shaking only moves it off and back
+pub fn shake_camera(
+    time: Res<Time>,
+    framing: Res<Framing>,
+    mut rng: ResMut<CosmeticRng>,
+    mut shake: ResMut<ScreenShake>,
+    mut q: Query<&mut Transform, With<MainCamera>>,
+) {
+    let amount = shake.trauma * shake.trauma;
+    shake.trauma = (shake.trauma - RECOVERY * time.delta_seconds()).max(0.0);
+
+    let rng = &mut rng.0;
+    for mut transform in q.iter_mut() {
+        let offset = if amount > 0.0 {
+            Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * MAX_OFFSET * amount
+        } else {
+            Vec2::ZERO
+        };
+        transform.translation.x = framing.center.x + offset.x;
+        transform.translation.y = framing.center.y + offset.y;
+        transform.scale = Vec3::new(framing.scale, framing.scale, 1.0);
+    }
+}
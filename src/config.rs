@@ -0,0 +1,459 @@
+use anyhow::{anyhow, bail, Context};
+use bevy::{log::Level, prelude::*};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    enemies::{EnemyRoster, Roster},
+    gameplay::{Enemy, Player, MAX_PLAYERS},
+    logging::LogConfig,
+    movement::Stamina,
+    net::{NetMode, NetSession},
+    pickups::PickupTimers,
+    replay::{Playback, Recorder, ReplayMode},
+    DifficultyTimer, EnemyTimer, FireballTimer, Wave,
+};
+
+pub const CONFIG_PATH: &str = "config.ron";
+//seconds between checks on the config file
+const WATCH_INTERVAL: f32 = 1.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    //how much faster enemies come and move than on normal
+    fn scale(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    fn parse(s: &str) -> anyhow::Result<Difficulty> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => bail!("Unknown difficulty {}, expected easy, normal or hard", s),
+        }
+    }
+}
+
+//tunables for the session, read from config.ron and then overridden from the command line
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub window_width: f32,
+    pub window_height: f32,
+    //only used until the options menu has saved a display mode, see run
+    pub fullscreen: bool,
+    //pixels per physics unit
+    pub phys_scale: f32,
+    pub player_speed: f32,
+    pub enemy_speed: f32,
+    //health each enemy spawns with, a projectile does 1 damage unless its pattern says otherwise
+    pub enemy_health: f32,
+    //pixels per second while dashing, and for how long
+    pub dash_speed: f32,
+    pub dash_time: f32,
+    //seconds from the start of one dash to when the next is ready
+    pub dash_cooldown: f32,
+    //seconds the player can't be hurt from the start of a dash
+    pub dash_invulnerability: f32,
+    //seconds the player can't be hurt again after being hit
+    pub hit_invulnerability: f32,
+    pub stamina_max: f32,
+    //stamina per second while sprinting, and per dash
+    pub sprint_drain: f32,
+    pub dash_stamina: f32,
+    //stamina per second coming back, once none has been spent for stamina_delay seconds
+    pub stamina_regen: f32,
+    pub stamina_delay: f32,
+    //how far a bomb reaches from the player in pixels, and what it does to each enemy in reach
+    pub bomb_radius: f32,
+    pub bomb_damage: f32,
+    pub starting_bombs: u32,
+    //the most bombs the player can carry, pickups are left lying around past this
+    pub max_bombs: u32,
+    //seconds between bomb pickups being dropped
+    pub bomb_pickup_interval: f32,
+    //seconds between item pickups being dropped
+    pub item_pickup_interval: f32,
+    //seconds between shots while firing
+    pub fire_rate: f32,
+    //seconds between enemies from each spawner, before the difficulty ramps it down
+    pub spawn_interval: f32,
+    //seconds between difficulty ramps
+    pub difficulty_step: f32,
+    pub difficulty: Difficulty,
+    //every run uses this seed when set, otherwise each one gets a random one
+    pub seed: Option<u64>,
+    pub log: LogConfig,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            window_width: 1280.0,
+            window_height: 720.0,
+            fullscreen: false,
+            phys_scale: 32.0,
+            player_speed: 500.0,
+            enemy_speed: 175.0,
+            enemy_health: 3.0,
+            dash_speed: 1400.0,
+            dash_time: 0.15,
+            dash_cooldown: 1.0,
+            dash_invulnerability: 0.3,
+            hit_invulnerability: 1.0,
+            stamina_max: 100.0,
+            sprint_drain: 30.0,
+            dash_stamina: 25.0,
+            stamina_regen: 40.0,
+            stamina_delay: 0.75,
+            bomb_radius: 300.0,
+            bomb_damage: 10.0,
+            starting_bombs: 1,
+            max_bombs: 3,
+            bomb_pickup_interval: 20.0,
+            item_pickup_interval: 45.0,
+            fire_rate: 0.1,
+            spawn_interval: 2.0,
+            difficulty_step: 30.0,
+            difficulty: Difficulty::Normal,
+            seed: None,
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<GameConfig> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        ron::from_str(&text).with_context(|| format!("{} is not a valid config", path.display()))
+    }
+
+    //read the config file and apply the command line on top of it
+    pub fn load(args: &Args) -> anyhow::Result<GameConfig> {
+        let mut config = match args.config {
+            Some(ref path) => GameConfig::read(path)?,
+            None if Path::new(CONFIG_PATH).exists() => GameConfig::read(CONFIG_PATH)?,
+            None => GameConfig::default(),
+        };
+        config.apply_args(args.seed, args.difficulty);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_args(&mut self, seed: Option<u64>, difficulty: Option<Difficulty>) {
+        if seed.is_some() {
+            self.seed = seed;
+        }
+        if let Some(difficulty) = difficulty {
+            self.difficulty = difficulty;
+        }
+    }
+
+    //report every bad value at once instead of making people fix them one run at a time
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let positive = [
+            ("window_width", self.window_width),
+            ("window_height", self.window_height),
+            ("phys_scale", self.phys_scale),
+            ("player_speed", self.player_speed),
+            ("enemy_health", self.enemy_health),
+            ("dash_speed", self.dash_speed),
+            ("dash_time", self.dash_time),
+            ("dash_cooldown", self.dash_cooldown),
+            ("dash_invulnerability", self.dash_invulnerability),
+            ("hit_invulnerability", self.hit_invulnerability),
+            ("stamina_max", self.stamina_max),
+            ("stamina_regen", self.stamina_regen),
+            ("bomb_radius", self.bomb_radius),
+            ("bomb_pickup_interval", self.bomb_pickup_interval),
+            ("item_pickup_interval", self.item_pickup_interval),
+            ("fire_rate", self.fire_rate),
+            ("spawn_interval", self.spawn_interval),
+            ("difficulty_step", self.difficulty_step),
+        ];
+        let mut problems: Vec<String> = positive
+            .iter()
+            .filter(|(_, value)| !(value.is_finite() && *value > 0.0))
+            .map(|(name, value)| format!("{} must be greater than 0, got {}", name, value))
+            .collect();
+
+        if self.starting_bombs > self.max_bombs {
+            problems.push(format!(
+                "starting_bombs can't be more than max_bombs ({}), got {}",
+                self.max_bombs, self.starting_bombs
+            ));
+        }
+
+        if self.log.max_size_kb == 0 {
+            problems.push("log.max_size_kb must be greater than 0".to_string());
+        }
+
+        let not_negative = [
+            ("enemy_speed", self.enemy_speed),
+            ("sprint_drain", self.sprint_drain),
+            ("dash_stamina", self.dash_stamina),
+            ("stamina_delay", self.stamina_delay),
+            ("bomb_damage", self.bomb_damage),
+        ];
+        problems.extend(
+            not_negative
+                .iter()
+                .filter(|(_, value)| !(value.is_finite() && *value >= 0.0))
+                .map(|(name, value)| format!("{} can't be negative, got {}", name, value)),
+        );
+
+        if !problems.is_empty() {
+            bail!("Invalid config:\n  {}", problems.join("\n  "));
+        }
+        Ok(())
+    }
+
+    //half the visible world, which is centred on the origin
+    pub fn half_size(&self) -> Vec2 {
+        Vec2::new(self.window_width, self.window_height) / 2.0
+    }
+
+    pub fn scaled_enemy_speed(&self) -> f32 {
+        self.enemy_speed * self.difficulty.scale()
+    }
+
+    pub fn scaled_spawn_interval(&self) -> f32 {
+        self.spawn_interval / self.difficulty.scale()
+    }
+}
+
+//--resources--//
+
+//the config file being played with, reread whenever it changes on disk
+pub struct ConfigWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    //kept so a reload doesn't undo what was passed on the command line
+    seed: Option<u64>,
+    difficulty: Option<Difficulty>,
+    timer: Timer,
+}
+
+impl ConfigWatch {
+    pub fn new(args: &Args) -> ConfigWatch {
+        let path = args
+            .config
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(CONFIG_PATH));
+        ConfigWatch {
+            modified: modified(&path),
+            path,
+            seed: args.seed,
+            difficulty: args.difficulty,
+            timer: Timer::from_seconds(WATCH_INTERVAL, true),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+//--systems--//
+
+//pick up edits to the config file and put them into the live run, tuning shouldn't need a restart
+//a replay, a recording or a networked run keeps what it started with, otherwise it can't be played back
+//or kept in step
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn reload_config(
+    time: Res<Time>,
+    watch: Option<ResMut<ConfigWatch>>,
+    mut config: ResMut<GameConfig>,
+    playback: Option<Res<Playback>>,
+    recorder: Option<Res<Recorder>>,
+    net: Option<Res<NetSession>>,
+    mut players: Query<(&mut Player, &mut Stamina, &mut FireballTimer)>,
+    mut enemies: Query<&mut Enemy>,
+    mut spawners: Query<&mut EnemyTimer>,
+    timers: Option<ResMut<PickupTimers>>,
+    difficulty: Option<ResMut<DifficultyTimer>>,
+    roster: Res<Roster>,
+    rosters: Res<Assets<EnemyRoster>>,
+    wave: Option<Res<Wave>>,
+) {
+    let mut watch = match watch {
+        Some(w) => w,
+        None => return,
+    };
+    if !watch.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let modified = modified(&watch.path);
+    if modified.is_none() || modified == watch.modified {
+        return;
+    }
+    watch.modified = modified;
+    if playback.is_some() || recorder.is_some() || net.is_some() {
+        warn!(
+            "{} changed, but this session can't take changes",
+            watch.path.display()
+        );
+        return;
+    }
+
+    let mut new = match GameConfig::read(&watch.path) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Not reloading config: {:#}", e);
+            return;
+        }
+    };
+    new.apply_args(watch.seed, watch.difficulty);
+    if let Err(e) = new.validate() {
+        error!("Not reloading config: {:#}", e);
+        return;
+    }
+    //these are only read once at startup
+    if new.window_width != config.window_width
+        || new.window_height != config.window_height
+        || new.phys_scale != config.phys_scale
+    {
+        warn!("Window size and phys_scale changes need a restart");
+    }
+
+    for (mut player, mut stamina, mut fire) in players.iter_mut() {
+        player.speed = new.player_speed;
+        stamina.max = new.stamina_max;
+        stamina.current = stamina.current.min(stamina.max);
+        fire.0.set_duration(Duration::from_secs_f32(new.fire_rate));
+    }
+    //live enemies keep the same share of their health, their kind still scales the new values
+    let roster = rosters.get(&roster.0);
+    let health_scale = new.enemy_health / config.enemy_health;
+    for mut enemy in enemies.iter_mut() {
+        let speed = roster
+            .and_then(|r| r.kind(&enemy.kind))
+            .map_or(1.0, |k| k.speed);
+        enemy.speed = new.scaled_enemy_speed() * speed;
+        enemy.health *= health_scale;
+    }
+    //spawners go at the pace of the wave the run is on
+    if let (Some(roster), Some(wave)) = (roster, wave) {
+        let secs = roster.spawn_interval(&new, wave.0);
+        for mut timer in spawners.iter_mut() {
+            timer.0.set_duration(Duration::from_secs_f32(secs));
+        }
+    }
+    if let Some(mut timers) = timers {
+        timers
+            .bomb
+            .set_duration(Duration::from_secs_f32(new.bomb_pickup_interval));
+        timers
+            .item
+            .set_duration(Duration::from_secs_f32(new.item_pickup_interval));
+    }
+    if let Some(mut difficulty) = difficulty {
+        difficulty
+            .0
+            .set_duration(Duration::from_secs_f32(new.difficulty_step));
+    }
+
+    *config = new;
+    info!("Reloaded {}", watch.path.display());
+}
+
+//everything that can be passed on the command line
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub replay: ReplayMode,
+    pub seed: Option<u64>,
+    pub windowed: bool,
+    pub difficulty: Option<Difficulty>,
+    pub headless: bool,
+    pub log_level: Option<Level>,
+    //how many players a run started from the command line has
+    pub players: usize,
+    pub net: NetMode,
+}
+
+impl Args {
+    pub fn parse() -> anyhow::Result<Args> {
+        let mut parsed = Args {
+            config: None,
+            replay: ReplayMode::Off,
+            seed: None,
+            windowed: false,
+            difficulty: None,
+            headless: false,
+            log_level: None,
+            players: 1,
+            net: NetMode::Off,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--config" => parsed.config = Some(value()?.into()),
+                "--record" => parsed.replay = ReplayMode::Record(value()?.into()),
+                "--replay" => parsed.replay = ReplayMode::Playback(value()?.into()),
+                "--seed" => {
+                    let seed = value()?;
+                    parsed.seed = Some(
+                        seed.parse()
+                            .with_context(|| format!("--seed {} is not a number", seed))?,
+                    );
+                }
+                "--difficulty" => parsed.difficulty = Some(Difficulty::parse(&value()?)?),
+                "--players" => {
+                    let players = value()?;
+                    parsed.players = match players.parse() {
+                        Ok(n) if (1..=MAX_PLAYERS).contains(&n) => n,
+                        _ => bail!(
+                            "--players {} is not a number from 1 to {}",
+                            players,
+                            MAX_PLAYERS
+                        ),
+                    };
+                }
+                "--host" => {
+                    let port = value()?;
+                    parsed.net = NetMode::Host(
+                        port.parse()
+                            .with_context(|| format!("--host {} is not a port", port))?,
+                    );
+                }
+                "--join" => parsed.net = NetMode::Join(value()?),
+                "--windowed" => parsed.windowed = true,
+                "--headless" => parsed.headless = true,
+                "--log-level" => {
+                    let level = value()?;
+                    parsed.log_level = Some(level.parse().map_err(|_| {
+                        anyhow!(
+                            "Unknown log level {}, expected error, warn, info, debug or trace",
+                            level
+                        )
+                    })?);
+                }
+                _ => bail!("Unknown argument {}", arg),
+            }
+        }
+        Ok(parsed)
+    }
+}
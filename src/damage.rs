@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+
+use crate::{menu::UiFont, status::Statuses};
+
+//drawn over particles so a burst doesn't hide the number
+const NUMBER_Z: f32 = 6.0;
+//seconds a damage number stays up, and how far it drifts in that time
+const NUMBER_LIFETIME: f32 = 0.6;
+const NUMBER_RISE: f32 = 40.0;
+const FLASH_TIME: f32 = 0.08;
+//sprite colour multiplies the texture, so anything over 1 pushes it towards white
+const FLASH_COLOR: Color = Color::rgb(8.0, 8.0, 8.0);
+
+//--components--//
+
+//the colour a sprite goes back to once nothing is tinting it
+#[derive(Component)]
+pub struct BaseColor(pub Color);
+
+//tints a sprite white for a moment, hitting it again while it's flashing starts it over
+#[derive(Component)]
+pub struct HitFlash(Timer);
+
+impl Default for HitFlash {
+    fn default() -> Self {
+        HitFlash(Timer::from_seconds(FLASH_TIME, false))
+    }
+}
+
+impl HitFlash {
+    pub fn restart(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[derive(Component)]
+pub struct DamageNumber(Timer);
+
+//--events--//
+
+//something took damage, shows how much where it happened
+pub struct DamageEvent {
+    pub position: Vec3,
+    pub amount: f32,
+}
+
+//--systems--//
+
+pub fn spawn_damage_numbers(
+    mut commands: Commands,
+    mut events: EventReader<DamageEvent>,
+    font: Res<UiFont>,
+) {
+    for ev in events.iter() {
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    ev.amount.to_string(),
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                transform: Transform::from_translation(ev.position.truncate().extend(NUMBER_Z)),
+                ..Default::default()
+            })
+            .insert(DamageNumber(Timer::from_seconds(NUMBER_LIFETIME, false)));
+    }
+}
+
+//drift numbers up and fade them out
+pub fn float_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q: Query<(Entity, &mut DamageNumber, &mut Transform, &mut Text)>,
+) {
+    for (ent, mut number, mut transform, mut text) in q.iter_mut() {
+        if number.0.tick(time.delta()).finished() {
+            commands.entity(ent).despawn();
+            continue;
+        }
+        transform.translation.y += NUMBER_RISE / NUMBER_LIFETIME * time.delta_seconds();
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(number.0.percent_left());
+        }
+    }
+}
+
+pub fn flash_hits(mut commands: Commands, time: Res<Time>, mut q: Query<(Entity, &mut HitFlash)>) {
+    for (ent, mut flash) in q.iter_mut() {
+        if flash.0.tick(time.delta()).finished() {
+            commands.entity(ent).remove::<HitFlash>();
+        }
+    }
+}
+
+//a hit flash shows over status effects, which show over the sprite's own colour
+pub fn tint_sprites(
+    mut q: Query<(
+        &BaseColor,
+        &mut TextureAtlasSprite,
+        Option<&HitFlash>,
+        Option<&Statuses>,
+    )>,
+) {
+    for (base, mut sprite, flash, statuses) in q.iter_mut() {
+        sprite.color = match (flash, statuses.and_then(|s| s.tint())) {
+            (Some(flash), _) if !flash.0.finished() => FLASH_COLOR,
+            (_, Some(tint)) => tint,
+            _ => base.0,
+        };
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::bail;
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use log::info;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    config::GameConfig,
+    damage::BaseColor,
+    gameplay::{Enemy, EnemySpawn, ENEMY_SIZE, MIN_SPAWN_INTERVAL},
+    EnemyTimer, GameRng, Hitbox, Wave,
+};
+
+//how one kind of enemy differs from the config's enemy_speed and enemy_health
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct EnemyKind {
+    //multipliers on the config values
+    pub speed: f32,
+    pub health: f32,
+    pub color: (f32, f32, f32),
+    //how much bigger than the sprite it's drawn, its hitbox grows with it
+    pub size: f32,
+}
+
+impl Default for EnemyKind {
+    fn default() -> Self {
+        EnemyKind {
+            speed: 1.0,
+            health: 1.0,
+            color: (0.94, 0.97, 1.0),
+            size: 1.5,
+        }
+    }
+}
+
+impl EnemyKind {
+    pub fn color(&self) -> Color {
+        Color::rgb(self.color.0, self.color.1, self.color.2)
+    }
+
+    pub fn hitbox(&self) -> Hitbox {
+        Hitbox(ENEMY_SIZE * self.size)
+    }
+}
+
+//what spawns during one wave
+#[derive(Deserialize, Clone, Debug)]
+pub struct WaveDef {
+    //kinds by name, every enemy is one of these picked at random, list one twice to make it more likely
+    pub enemies: Vec<String>,
+    //seconds between spawns as a fraction of the config's spawn_interval
+    pub interval: f32,
+}
+
+//every kind of enemy by name and the waves they come in, authored as a .enemies.ron file in assets/enemies
+//sorted so the same names always come out in the same order
+#[derive(Deserialize, TypeUuid, Clone)]
+#[uuid = "8c41d2e7-5b06-4f3a-a9e1-37d0c6b84f25"]
+pub struct EnemyRoster {
+    pub kinds: BTreeMap<String, EnemyKind>,
+    //the first wave first, the last one goes on for the rest of the run
+    pub waves: Vec<WaveDef>,
+}
+
+impl EnemyRoster {
+    fn check(&self) -> anyhow::Result<()> {
+        for (name, kind) in self.kinds.iter() {
+            if kind.speed < 0.0 || kind.health <= 0.0 || kind.size <= 0.0 {
+                bail!(
+                    "{} needs a speed of at least 0, and health and size above 0",
+                    name
+                );
+            }
+        }
+        if self.waves.is_empty() {
+            bail!("There are no waves");
+        }
+        for (i, wave) in self.waves.iter().enumerate() {
+            if wave.enemies.is_empty() {
+                bail!("Wave {} has no enemies", i + 1);
+            }
+            if wave.interval <= 0.0 {
+                bail!("Wave {} needs an interval above 0", i + 1);
+            }
+            if let Some(name) = wave.enemies.iter().find(|e| !self.kinds.contains_key(*e)) {
+                bail!("Wave {} has {}, which isn't a kind of enemy", i + 1, name);
+            }
+        }
+        Ok(())
+    }
+
+    //counted from 0 like the Wave resource
+    pub fn wave(&self, wave: u32) -> &WaveDef {
+        let last = self.waves.len() - 1;
+        &self.waves[(wave as usize).min(last)]
+    }
+
+    pub fn kind(&self, name: &str) -> Option<&EnemyKind> {
+        self.kinds.get(name)
+    }
+
+    //a kind of enemy for the next spawn on this wave
+    pub fn pick(&self, wave: u32, rng: &mut GameRng) -> (&str, &EnemyKind) {
+        let wave = self.wave(wave);
+        let name = wave.enemies.choose(&mut rng.0).unwrap_or(&wave.enemies[0]);
+        (name, &self.kinds[name.as_str()])
+    }
+
+    //how long each spawner waits between enemies on this wave
+    pub fn spawn_interval(&self, config: &GameConfig, wave: u32) -> f32 {
+        (config.scaled_spawn_interval() * self.wave(wave).interval).max(MIN_SPAWN_INTERVAL)
+    }
+}
+
+#[derive(Default)]
+pub struct EnemyRosterLoader;
+
+impl AssetLoader for EnemyRosterLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let roster = ron::de::from_bytes::<EnemyRoster>(bytes)?;
+            roster.check()?;
+            load_context.set_default_asset(LoadedAsset::new(roster));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemies.ron"]
+    }
+}
+
+//--resources--//
+
+pub struct Roster(pub Handle<EnemyRoster>);
+
+//--systems--//
+
+//put edits to the roster into the enemies and spawners already in the run, anything spawned after
+//reads the file as it is. live enemies keep the same share of their health, like a config reload
+//applied is the roster as it was before the edit, to tell which kinds changed
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn apply_roster(
+    mut events: EventReader<AssetEvent<EnemyRoster>>,
+    rosters: Res<Assets<EnemyRoster>>,
+    roster: Res<Roster>,
+    mut applied: Local<Option<EnemyRoster>>,
+    config: Res<GameConfig>,
+    wave: Option<Res<Wave>>,
+    mut enemies: Query<(&mut Enemy, &mut BaseColor, &mut Transform, &mut Hitbox)>,
+    mut spawners: Query<&mut EnemyTimer, With<EnemySpawn>>,
+) {
+    for ev in events.iter() {
+        let handle = match ev {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != roster.0 {
+            continue;
+        }
+        let new = match rosters.get(handle) {
+            Some(r) => r.clone(),
+            None => continue,
+        };
+        info!(
+            "Loaded {} kinds of enemy in {} waves",
+            new.kinds.len(),
+            new.waves.len()
+        );
+
+        if let Some(old) = applied.take() {
+            for (mut enemy, mut color, mut tr, mut hitbox) in enemies.iter_mut() {
+                //a kind taken out of the file leaves the ones already out as they are
+                let (was, kind) = match (old.kind(&enemy.kind), new.kind(&enemy.kind)) {
+                    (Some(was), Some(kind)) => (was, kind),
+                    _ => continue,
+                };
+                if was == kind {
+                    continue;
+                }
+                enemy.speed = config.scaled_enemy_speed() * kind.speed;
+                enemy.health *= kind.health / was.health;
+                color.0 = kind.color();
+                tr.scale = Vec3::splat(kind.size);
+                *hitbox = kind.hitbox();
+            }
+            if let Some(ref wave) = wave {
+                let secs = new.spawn_interval(&config, wave.0);
+                for mut timer in spawners.iter_mut() {
+                    timer.0.set_duration(Duration::from_secs_f32(secs));
+                }
+            }
+        }
+        *applied = Some(new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_roster_loads() {
+        let bytes = std::fs::read("assets/enemies/game.enemies.ron").unwrap();
+        let roster = ron::de::from_bytes::<EnemyRoster>(&bytes).unwrap();
+        roster.check().unwrap();
+
+        //the last wave carries on, and spawners bottom out at the minimum
+        let config = GameConfig::default();
+        let last = roster.waves.len() as u32 - 1;
+        assert_eq!(
+            roster.spawn_interval(&config, last + 10),
+            roster.spawn_interval(&config, last)
+        );
+        assert!(roster.spawn_interval(&config, last) >= MIN_SPAWN_INTERVAL);
+    }
+
+    #[test]
+    fn waves_need_known_kinds() {
+        let roster = ron::de::from_str::<EnemyRoster>(
+            r#"(kinds: {"walker": ()}, waves: [(enemies: ["runner"], interval: 1.0)])"#,
+        )
+        .unwrap();
+        assert!(roster.check().is_err());
+    }
+}
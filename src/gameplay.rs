@@ -1,34 +1,46 @@
-use bevy::prelude::*;
-use bevy_rapier2d::prelude::{RigidBodyVelocity, RigidBodyVelocityComponent};
-use log::{debug, error, info, trace, warn};
-use rand::random;
+use bevy::{math::const_vec2, prelude::*};
+use bevy_rapier2d::prelude::RigidBodyVelocityComponent;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 use crate::{
-    attacks::{self, Attack},
+    animation,
+    config::GameConfig,
+    damage::BaseColor,
+    enemies::{EnemyKind, EnemyRoster, Roster},
+    items::{Hook, Inventory, ItemTrigger},
+    movement::{Dash, Invulnerable, Stamina, VelocityModifiers},
+    particles::{Effect, ParticleEvent},
+    pool::EntityPool,
+    settings::{Action, KeyBindings},
+    sfx::SoundEvent,
+    timestep::Interpolated,
     Collider, CurrentAttack, DifficultyTimer, EnemySpr, EnemyTimer, FireballSpr, FireballTimer,
-    Hitbox, TEX_SIZE, WIN_SIZE,
+    GameRng, RunTime, StepTime, Wave,
 };
 
-#[derive(Component)]
-pub struct Powerup {
-    attack: Box<dyn Attack + Send + Sync>,
-}
+//the most players a run can have, one on the keyboard and one on a gamepad
+pub const MAX_PLAYERS: usize = 2;
+//how far out from the player the reticle sits when aiming
+const RETICLE_DISTANCE: f32 = 100.0;
+//stick movement smaller than this is ignored
+const DEADZONE: f32 = 0.2;
+//spawners never go faster than this however far the run gets
+pub const MIN_SPAWN_INTERVAL: f32 = 0.5;
+//one frame of enemy.png, an enemy's kind scales it and its hitbox up from this
+pub const ENEMY_SIZE: Vec2 = const_vec2!([14.0, 16.0]);
 
 #[derive(Component)]
 pub struct Player {
     pub speed: f32,
-    mod_y: f32,
-    mod_x: f32,
+    //which player this is, 0 is on the keyboard
+    pub slot: usize,
 }
 
 impl Player {
-    pub fn new(speed: f32) -> Player {
-        Player {
-            speed: speed,
-            mod_x: 0.0,
-            mod_y: 0.0,
-        }
+    pub fn new(slot: usize, speed: f32) -> Player {
+        Player { speed, slot }
     }
 }
 
@@ -36,204 +48,336 @@ impl Player {
 pub struct Fireball {
     pub origin: Vec3,
     pub target: Vec3,
+    pub speed: f32,
+    pub damage: f32,
+    //slot of the player who fired it, their items decide what it does
+    pub owner: usize,
 }
 
 #[derive(Component)]
 pub struct Enemy {
+    //its name in the enemy roster
+    pub kind: String,
     pub speed: f32,
+    pub health: f32,
 }
 
+//where the player with this slot is aiming
 #[derive(Component)]
-pub struct Reticle;
+pub struct Reticle(pub usize);
 
 #[derive(Component)]
 pub struct EnemySpawn;
 
-#[derive(Default)]
-pub struct Elapsed(f32);
+//the actions a player is taking this frame, read from the keyboard, a gamepad or a replay
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    pub movement: Vec2,
+    pub sprint: bool,
+    pub fire: bool,
+    #[serde(default)]
+    pub dash: bool,
+    #[serde(default)]
+    pub bomb: bool,
+    //which way the reticle goes from the player, left where it was while zero
+    #[serde(default)]
+    pub aim: Vec2,
+}
 
-//move the sprite
-pub fn move_sys(
-    time: Res<Time>,
-    input: Res<Input<KeyCode>>,
-    mut q: Query<(&Player, &mut Transform)>,
-    mut ret: Query<&mut Transform, (With<Reticle>, Without<Player>)>,
-    mut player_vel: Query<&mut RigidBodyVelocityComponent, With<Player>>,
-) {
-    for (p, mut transform) in q.iter_mut() {
-        let mut x_dir = 0.0;
-        let mut y_dir = 0.0;
-        let mut sprint = 1.0;
+impl PlayerInput {
+    const IDLE: PlayerInput = PlayerInput {
+        movement: Vec2::ZERO,
+        sprint: false,
+        fire: false,
+        dash: false,
+        bomb: false,
+        aim: Vec2::ZERO,
+    };
+
+    pub fn from_keys(input: &Input<KeyCode>, keys: &KeyBindings) -> PlayerInput {
+        let mut movement = Vec2::ZERO;
 
-        if input.pressed(KeyCode::A) {
-            x_dir -= 1.0;
+        if keys.pressed(input, Action::Left) {
+            movement.x -= 1.0;
         }
 
-        if input.pressed(KeyCode::D) {
-            x_dir += 1.0;
+        if keys.pressed(input, Action::Right) {
+            movement.x += 1.0;
+        }
+
+        if keys.pressed(input, Action::Up) {
+            movement.y += 1.0;
+        }
+
+        if keys.pressed(input, Action::Down) {
+            movement.y -= 1.0;
+        }
+
+        let mut aim = Vec2::ZERO;
+        if keys.pressed(input, Action::FireLeft) {
+            aim.x -= 1.0;
+        }
+        if keys.pressed(input, Action::FireRight) {
+            aim.x += 1.0;
+        }
+        if keys.pressed(input, Action::FireUp) {
+            aim.y += 1.0;
+        }
+        if keys.pressed(input, Action::FireDown) {
+            aim.y -= 1.0;
         }
 
-        if input.pressed(KeyCode::W) {
-            y_dir += 1.0;
+        PlayerInput {
+            movement,
+            sprint: keys.pressed(input, Action::Sprint),
+            fire: keys.pressed(input, Action::FireRight)
+                || keys.pressed(input, Action::FireLeft)
+                || keys.pressed(input, Action::FireUp)
+                || keys.pressed(input, Action::FireDown),
+            dash: keys.pressed(input, Action::Dash),
+            bomb: keys.pressed(input, Action::Bomb),
+            aim,
         }
+    }
+
+    //left stick moves, right stick aims and fires, the right trigger fires where it last aimed
+    pub fn from_gamepad(
+        gamepad: Gamepad,
+        buttons: &Input<GamepadButton>,
+        axes: &Axis<GamepadAxis>,
+    ) -> PlayerInput {
+        let stick = |x, y| {
+            let v = Vec2::new(
+                axes.get(GamepadAxis(gamepad, x)).unwrap_or(0.0),
+                axes.get(GamepadAxis(gamepad, y)).unwrap_or(0.0),
+            );
+            if v.length() < DEADZONE {
+                Vec2::ZERO
+            } else {
+                v.clamp_length_max(1.0)
+            }
+        };
+        let pressed = |button| buttons.pressed(GamepadButton(gamepad, button));
 
-        if input.pressed(KeyCode::S) {
-            y_dir -= 1.0;
+        let aim = stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+        PlayerInput {
+            movement: stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+            sprint: pressed(GamepadButtonType::LeftTrigger2),
+            fire: aim != Vec2::ZERO || pressed(GamepadButtonType::RightTrigger2),
+            dash: pressed(GamepadButtonType::South),
+            bomb: pressed(GamepadButtonType::East),
+            aim,
         }
+    }
+}
+
+//this step's input for every player in the run, by slot
+#[derive(Default)]
+pub struct PlayerInputs(pub Vec<PlayerInput>);
+
+impl PlayerInputs {
+    pub fn get(&self, slot: usize) -> &PlayerInput {
+        self.0.get(slot).unwrap_or(&PlayerInput::IDLE)
+    }
+}
+
+//how many people are playing the next run, picked from the menu or the command line
+pub struct PlayerCount(pub usize);
+
+//move the sprite, rapier takes velocity in physics units per second
+//a dash goes off when its button goes down, holding it doesn't keep dashing
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn move_sys(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    time: Res<StepTime>,
+    inputs: Res<PlayerInputs>,
+    mut q: Query<(
+        Entity,
+        &Player,
+        &mut Dash,
+        &mut Stamina,
+        Option<&Inventory>,
+        &VelocityModifiers,
+        Option<&mut Invulnerable>,
+        &mut RigidBodyVelocityComponent,
+    )>,
+) {
+    let delta = time.delta_seconds();
+    for (ent, p, mut dash, mut stamina, inventory, modifiers, invulnerable, mut vel) in q.iter_mut()
+    {
+        let input = inputs.get(p.slot);
+        let stats = inventory.map(|i| i.stats).unwrap_or_default();
+        dash.tick(delta);
+        stamina.recover(delta, &config, stats.stamina_regen);
 
-        if input.pressed(KeyCode::LShift) {
-            sprint = 1.5;
+        if dash.press(input.dash)
+            && stamina.current >= config.dash_stamina
+            && dash.start(input.movement, &config)
+        {
+            stamina.spend(config.dash_stamina);
+            match invulnerable {
+                Some(mut i) => i.0 = i.0.max(config.dash_invulnerability),
+                None => {
+                    commands
+                        .entity(ent)
+                        .insert(Invulnerable(config.dash_invulnerability));
+                }
+            }
         }
-        let x_delt = time.delta_seconds() * p.speed * (x_dir + p.mod_x) * sprint;
-        let y_delt = time.delta_seconds() * p.speed * (y_dir + p.mod_y) * sprint;
 
-        let mut vel = player_vel.single_mut();
-        vel.linvel = Vec2::new(x_delt, y_delt).into();
+        //sprinting only costs anything while actually moving
+        let sprinting = input.sprint
+            && input.movement != Vec2::ZERO
+            && stamina.drain(config.sprint_drain * delta);
+        let sprint = if sprinting { 1.5 } else { 1.0 };
+        let own = dash
+            .velocity(&config)
+            .unwrap_or(input.movement * p.speed * stats.move_speed * sprint);
+
+        vel.linvel = (modifiers.apply(own) / config.phys_scale).into();
+    }
+}
+
+//put each player's reticle out the way they're aiming, it stays put while they aren't
+pub fn aim_reticles(
+    inputs: Res<PlayerInputs>,
+    players: Query<(&Player, &Transform)>,
+    mut reticles: Query<(&Reticle, &mut Transform), Without<Player>>,
+) {
+    for (reticle, mut tr) in reticles.iter_mut() {
+        let aim = inputs.get(reticle.0).aim;
+        if aim == Vec2::ZERO {
+            continue;
+        }
+        if let Some((_, player_tr)) = players.iter().find(|(p, _)| p.slot == reticle.0) {
+            tr.translation = player_tr.translation + aim.extend(0.0) * RETICLE_DISTANCE;
+        }
     }
-    // let translation = &mut transform.translation;
-
-    // translation.x += time.delta_seconds() * p.speed * (x_dir + p.mod_x) * sprint;
-    // translation.y += time.delta_seconds() * p.speed * (y_dir + p.mod_y) * sprint;
-
-    // //confine player to the screen
-    // translation.x = translation
-    //     .x
-    //     .min(1280.0 / 2.0 - TEX_SIZE)
-    //     .max(-(1280.0 / 2.0 - TEX_SIZE));
-    // translation.y = translation
-    //     .y
-    //     .min(720.0 / 2.0 - TEX_SIZE)
-    //     .max(-(720.0 / 2.0 - TEX_SIZE));
-
-    //     //move the reticle
-    //     let ret_pos = &mut ret.iter_mut().next().unwrap().translation;
-
-    //     if input.pressed(KeyCode::Up) {
-    //         ret_pos.y = 100.0;
-    //     } else if input.pressed(KeyCode::Down) {
-    //         ret_pos.y = -100.0;
-    //     }
-
-    //     if input.pressed(KeyCode::Left) {
-    //         ret_pos.x = -100.0;
-    //     } else if input.pressed(KeyCode::Right) {
-    //         ret_pos.x = 100.0;
-    //     }
-
-    //     if !input.pressed(KeyCode::Up) && !input.pressed(KeyCode::Down) {
-    //         ret_pos.y = 0.0;
-    //     }
-    //     if !input.pressed(KeyCode::Left) && !input.pressed(KeyCode::Right) {
-    //         ret_pos.x = 0.0;
-    //     }
-
-    //     ret_pos.x += translation.x;
-    //     ret_pos.y += translation.y;
-    // }
 }
 
-//spawn a fireball while the left mouse button is held down, on a 0.1s timer
+//fire each player's attack at their reticle while they hold fire, on their own fire_rate timer
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_fireball(
     mut commands: Commands,
-    input: Res<Input<KeyCode>>,
+    inputs: Res<PlayerInputs>,
     fire_sp: Res<FireballSpr>,
-    player: Query<&Transform, With<Player>>,
-    ret: Query<&Transform, With<Reticle>>,
-    time: Res<Time>,
-    mut timer: ResMut<FireballTimer>,
-    attack: ResMut<CurrentAttack>,
+    mut players: Query<(&Player, &Transform, &mut FireballTimer, &CurrentAttack)>,
+    ret: Query<(&Reticle, &Transform)>,
+    time: Res<StepTime>,
+    mut sounds: EventWriter<SoundEvent>,
+    mut effects: EventWriter<ParticleEvent>,
+    mut triggers: EventWriter<ItemTrigger>,
 ) {
-    if !timer.0.tick(time.delta()).just_finished() && !timer.0.paused() {
-        return;
-    }
+    for (player, transform, mut timer, attack) in players.iter_mut() {
+        if !timer.0.tick(time.delta()).just_finished() && !timer.0.paused() {
+            continue;
+        }
 
-    if input.pressed(KeyCode::Right)
-        || input.pressed(KeyCode::Left)
-        || input.pressed(KeyCode::Up)
-        || input.pressed(KeyCode::Down)
-    {
+        if !inputs.get(player.slot).fire {
+            timer.0.pause();
+            timer.0.reset();
+            continue;
+        }
         timer.0.unpause();
 
-        for transform in player.iter() {
-            let origin = transform.translation;
-            let target = {
-                let tr = ret.single();
-                debug!("Fireball target: {}", tr.translation);
-                tr.translation
-            };
+        let origin = transform.translation;
+        let target = match ret.iter().find(|(r, _)| r.0 == player.slot) {
+            Some((_, tr)) => tr.translation,
+            None => continue,
+        };
+        debug!("Fireball target: {}", target);
 
-            attack.0.attack(&mut commands, &origin, &target, &fire_sp.0);
-        }
-    } else {
-        timer.0.pause();
-        timer.0.reset();
+        attack
+            .0
+            .attack(&mut commands, &origin, &target, &fire_sp.0, player.slot);
+        sounds.send(SoundEvent::Shoot);
+        effects.send(ParticleEvent::new(
+            Effect::Muzzle,
+            origin,
+            (target - origin).truncate(),
+        ));
+        triggers.send(ItemTrigger {
+            hook: Hook::Shoot,
+            player: Some(player.slot),
+            position: origin,
+            direction: (target - origin).truncate(),
+            target: None,
+        });
     }
 }
 
-//spawn enemies from each active spawner
+//kind is how it looks, enemy already has the speed and health it comes in with
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    pool: &mut EntityPool<Enemy>,
+    sprite: &Handle<TextureAtlas>,
+    transform: Transform,
+    enemy: Enemy,
+    kind: &EnemyKind,
+) -> Entity {
+    pool.acquire(commands)
+        .insert_bundle(SpriteSheetBundle {
+            texture_atlas: sprite.clone(),
+            transform: transform.with_scale(Vec3::splat(kind.size)),
+            sprite: TextureAtlasSprite {
+                color: kind.color(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(BaseColor(kind.color()))
+        .insert(animation::enemy())
+        .insert(enemy)
+        .insert(Interpolated::new(transform.translation))
+        .insert(Collider::Enemy)
+        .insert(kind.hitbox())
+        .id()
+}
+
+pub fn tick_run_time(time: Res<StepTime>, mut run_time: ResMut<RunTime>) {
+    run_time.0 += time.delta_seconds();
+}
+
+//spawn enemies from each active spawner, what comes and how often is down to the wave in the enemy roster
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_enemies(
     mut commands: Commands,
-    time: Res<Time>,
+    config: Res<GameConfig>,
+    time: Res<StepTime>,
     enemy: Res<EnemySpr>,
+    roster: Res<Roster>,
+    rosters: Res<Assets<EnemyRoster>>,
+    mut rng: ResMut<GameRng>,
     mut diff: ResMut<DifficultyTimer>,
+    mut wave: ResMut<Wave>,
     mut q: Query<(&Transform, &mut EnemyTimer)>,
+    mut pool: ResMut<EntityPool<Enemy>>,
+    mut sounds: EventWriter<SoundEvent>,
 ) {
-    diff.0.tick(time.delta());
+    let roster = match rosters.get(&roster.0) {
+        Some(r) => r,
+        None => return,
+    };
+    let wave_up = diff.0.tick(time.delta()).just_finished();
+    if wave_up {
+        wave.0 += 1;
+        sounds.send(SoundEvent::LevelUp);
+        info!("Wave {}", wave.0);
+    }
     for (transform, mut timer) in q.iter_mut() {
         if timer.0.tick(time.delta()).finished() {
-            commands
-                .spawn_bundle(SpriteBundle {
-                    texture: enemy.0.clone(),
-                    transform: transform.with_scale(Vec3::splat(1.5)),
-                    sprite: Sprite {
-                        color: Color::ALICE_BLUE,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(Enemy { speed: 175.0 })
-                .insert(Collider::Enemy)
-                .insert(Hitbox(Vec2::new(21.0, 24.0)));
-        }
-        if diff.0.finished() {
-            let dur = timer.0.duration();
-            if dur <= Duration::from_secs_f32(0.5) {
-                info!("DIFFICULTY MAX!!!");
-            } else {
-                timer.0.set_duration(dur - Duration::from_secs_f32(0.5));
-                info!("Difficulty went up!");
-            }
+            let (name, kind) = roster.pick(wave.0, &mut rng);
+            let stats = Enemy {
+                kind: name.to_string(),
+                speed: config.scaled_enemy_speed() * kind.speed,
+                health: config.enemy_health * kind.health,
+            };
+            spawn_enemy(&mut commands, &mut pool, &enemy.0, *transform, stats, kind);
+        }
+        if wave_up {
+            let secs = roster.spawn_interval(&config, wave.0);
+            timer.0.set_duration(Duration::from_secs_f32(secs));
         }
-    }
-}
-
-pub fn spawn_powerups(
-    commands: &mut Commands,
-    time: Res<Time>,
-    mut elapsed: Local<Elapsed>,
-    powerup: Res<EnemySpr>,
-) {
-    elapsed.0 = elapsed.0 + time.delta_seconds();
-    if elapsed.0 >= 30.0 {
-        let mut tr = Transform::from_translation(Vec3::new(
-            random::<i32>().min(WIN_SIZE.0 as i32) as f32,
-            random::<i32>().min(WIN_SIZE.1 as i32) as f32,
-            0.0,
-        ));
-        tr.scale = Vec3::splat(0.5);
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: powerup.0.clone(),
-                transform: tr,
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(7.0, 8.0)),
-                    color: Color::RED,
-                    ..Default::default()
-                },
-                ..Default::default()
-            })
-            .insert(Powerup {
-                attack: Box::new(attacks::Split),
-            });
     }
 }
@@ -0,0 +1,431 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use log::debug;
+use std::collections::HashSet;
+
+use crate::{
+    config::GameConfig,
+    damage::{DamageEvent, HitFlash},
+    gameplay::{Enemy, Fireball, Player, MAX_PLAYERS},
+    items::{Hook, Inventory, ItemTrigger},
+    movement::{Invulnerable, Modifier, ModifierSource, VelocityModifiers},
+    particles::{Effect, ParticleEvent},
+    patterns::{self, OnDeath},
+    pool::EntityPool,
+    sfx::SoundEvent,
+    status::{Inflicts, Statuses},
+    Collider, PlayerHitEvent, Score,
+};
+
+//how hard and for how long the player gets shoved away from whatever hit it
+const KNOCKBACK_SPEED: f32 = 600.0;
+const KNOCKBACK_TIME: f32 = 0.15;
+
+//--components--//
+
+//on its way out, pooled or despawned at the start of the next step and ignored until then
+#[derive(Component)]
+pub struct Despawning;
+
+//fired by enemies, hits the player instead of them and is wiped out by a bomb
+#[derive(Component)]
+pub struct Hostile;
+
+//how many more enemies a projectile goes through, and the ones it already has
+#[derive(Component)]
+pub struct Pierce {
+    pub left: u32,
+    hit: Vec<Entity>,
+}
+
+impl Pierce {
+    pub fn new(left: u32) -> Pierce {
+        Pierce {
+            left,
+            hit: Vec::new(),
+        }
+    }
+}
+
+//--events--//
+
+//two things overlapped, sent by the collision systems and only acted on by resolve_hits
+pub struct Hit {
+    pub attacker: Entity,
+    pub victim: Entity,
+}
+
+//everything a hit can set off
+#[derive(SystemParam)]
+pub struct HitOutcomes<'w, 's> {
+    score: ResMut<'w, Score>,
+    player_hit: EventWriter<'w, 's, PlayerHitEvent>,
+    pub sounds: EventWriter<'w, 's, SoundEvent>,
+    pub effects: EventWriter<'w, 's, ParticleEvent>,
+    damage: EventWriter<'w, 's, DamageEvent>,
+    pub triggers: EventWriter<'w, 's, ItemTrigger>,
+}
+
+impl<'w, 's> HitOutcomes<'w, 's> {
+    //take health off an enemy and show it, returns whether that killed it
+    //by is the slot of the player who did it, if anyone in particular did
+    #[allow(clippy::too_many_arguments)]
+    pub fn hurt(
+        &mut self,
+        commands: &mut Commands,
+        ent: Entity,
+        enemy: &mut Enemy,
+        at: Vec3,
+        amount: f32,
+        heading: Vec2,
+        by: Option<usize>,
+    ) -> bool {
+        enemy.health -= amount;
+        self.damage.send(DamageEvent {
+            position: at,
+            amount,
+        });
+        if enemy.health > 0.0 {
+            return false;
+        }
+
+        commands.entity(ent).insert(Despawning);
+        self.score.0 += 1;
+        self.sounds.send(SoundEvent::EnemyDeath);
+        self.effects
+            .send(ParticleEvent::new(Effect::Death, at, heading));
+        self.triggers.send(ItemTrigger {
+            hook: Hook::Kill,
+            player: by,
+            position: at,
+            direction: heading,
+            target: Some(ent),
+        });
+        true
+    }
+}
+
+//--systems--//
+
+//apply each step's hits once per entity, whatever else it overlapped stops counting once it's gone
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn resolve_hits(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    mut hits: EventReader<Hit>,
+    mut projectiles: Query<(
+        &Transform,
+        &Fireball,
+        Option<&OnDeath>,
+        Option<&mut Pierce>,
+        Option<&Inflicts>,
+    )>,
+    victims: Query<(&Transform, &Collider)>,
+    mut enemies: Query<(&mut Enemy, Option<&mut HitFlash>, Option<&mut Statuses>)>,
+    mut players: Query<(
+        &Player,
+        &Transform,
+        &mut VelocityModifiers,
+        Option<&Invulnerable>,
+        Option<&Inventory>,
+    )>,
+    mut outcomes: HitOutcomes,
+) {
+    //each player's projectiles hit as hard as their items make them
+    let mut damage_scale = [1.0; MAX_PLAYERS];
+    for (player, _, _, _, inventory) in players.iter() {
+        if let (Some(scale), Some(inventory)) = (damage_scale.get_mut(player.slot), inventory) {
+            *scale = inventory.stats.damage;
+        }
+    }
+    let mut gone = HashSet::new();
+    //players already hurt this step, their invulnerability only goes on at the end of it
+    let mut hurt = HashSet::new();
+    for hit in hits.iter() {
+        if gone.contains(&hit.attacker) || gone.contains(&hit.victim) {
+            continue;
+        }
+
+        //an enemy or one of their projectiles got the player, which knocks it back unless it's dashing through,
+        //then it gets a moment to get clear before anything else can hurt it
+        if let Ok((_, player_tr, mut modifiers, invulnerable, _)) = players.get_mut(hit.victim) {
+            if invulnerable.is_some() || !hurt.insert(hit.victim) {
+                continue;
+            }
+            gone.insert(hit.attacker);
+            commands.entity(hit.attacker).insert(Despawning);
+            commands
+                .entity(hit.victim)
+                .insert(Invulnerable(config.hit_invulnerability));
+            outcomes.player_hit.send(PlayerHitEvent(hit.victim));
+            if let Ok((tr, _)) = victims.get(hit.attacker) {
+                let away = (player_tr.translation - tr.translation)
+                    .truncate()
+                    .normalize_or_zero();
+                modifiers.add(
+                    ModifierSource::Knockback,
+                    Modifier::Push(away * KNOCKBACK_SPEED),
+                    Some(KNOCKBACK_TIME),
+                );
+            }
+            continue;
+        }
+
+        let (ball_tr, ball, on_death, mut pierce, inflicts) =
+            match projectiles.get_mut(hit.attacker) {
+                Ok(p) => p,
+                Err(_) => continue,
+            };
+        let (tr, col) = match victims.get(hit.victim) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let heading = (ball.target - ball.origin).truncate();
+
+        let spent = match *col {
+            Collider::Enemy => {
+                if pierce.as_ref().is_some_and(|p| p.hit.contains(&hit.victim)) {
+                    continue;
+                }
+                let (mut enemy, flash, statuses) = match enemies.get_mut(hit.victim) {
+                    Ok(e) if e.0.health > 0.0 => e,
+                    //already killed this step by something else
+                    _ => continue,
+                };
+                let killed = outcomes.hurt(
+                    &mut commands,
+                    hit.victim,
+                    &mut enemy,
+                    tr.translation,
+                    ball.damage * damage_scale.get(ball.owner).copied().unwrap_or(1.0),
+                    heading,
+                    Some(ball.owner),
+                );
+                outcomes.triggers.send(ItemTrigger {
+                    hook: Hook::Hit,
+                    player: Some(ball.owner),
+                    position: tr.translation,
+                    direction: heading,
+                    target: Some(hit.victim),
+                });
+
+                if killed {
+                    gone.insert(hit.victim);
+                } else {
+                    match flash {
+                        Some(mut flash) => flash.restart(),
+                        None => {
+                            commands.entity(hit.victim).insert(HitFlash::default());
+                        }
+                    }
+                    match (inflicts, statuses) {
+                        (Some(inflicts), Some(mut statuses)) => {
+                            inflicts.0.iter().for_each(|def| statuses.apply(def))
+                        }
+                        (Some(inflicts), None) => {
+                            commands
+                                .entity(hit.victim)
+                                .insert(Statuses::from_defs(&inflicts.0));
+                        }
+                        _ => (),
+                    }
+                }
+
+                match pierce {
+                    Some(ref mut p) if p.left > 0 => {
+                        p.left -= 1;
+                        p.hit.push(hit.victim);
+                        false
+                    }
+                    _ => true,
+                }
+            }
+            Collider::Solid => {
+                //sprays back off whatever it hit
+                outcomes.effects.send(ParticleEvent::new(
+                    Effect::Impact,
+                    ball_tr.translation,
+                    -heading,
+                ));
+                true
+            }
+            Collider::Projectile => false,
+        };
+
+        if spent {
+            gone.insert(hit.attacker);
+            commands.entity(hit.attacker).insert(Despawning);
+            if let Some(on_death) = on_death {
+                patterns::fire_on_death(&mut commands, on_death, ball, ball_tr.translation);
+            }
+        }
+    }
+}
+
+//the one place anything that was hit or left the screen actually goes away
+#[allow(clippy::type_complexity)]
+pub fn despawn_marked(
+    mut commands: Commands,
+    q: Query<(Entity, Option<&Fireball>, Option<&Enemy>), With<Despawning>>,
+    mut projectiles: ResMut<EntityPool<Fireball>>,
+    mut enemies: ResMut<EntityPool<Enemy>>,
+) {
+    for (ent, ball, enemy) in q.iter() {
+        if ball.is_some() {
+            projectiles.release(&mut commands, ent);
+        } else if enemy.is_some() {
+            enemies.release(&mut commands, ent);
+        } else {
+            commands.entity(ent).despawn();
+        }
+        debug!("Removed {:?}", ent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::{ActiveEmitter, Emitter};
+    use bevy::app::Events;
+
+    //a world with everything resolve_hits writes to, and a stage that runs just it
+    fn setup() -> (World, SystemStage) {
+        let mut world = World::new();
+        world.insert_resource(GameConfig::default());
+        world.insert_resource(Score::default());
+        world.insert_resource(Events::<Hit>::default());
+        world.insert_resource(Events::<PlayerHitEvent>::default());
+        world.insert_resource(Events::<SoundEvent>::default());
+        world.insert_resource(Events::<ParticleEvent>::default());
+        world.insert_resource(Events::<DamageEvent>::default());
+        world.insert_resource(Events::<ItemTrigger>::default());
+        let stage = SystemStage::single_threaded().with_system(resolve_hits);
+        (world, stage)
+    }
+
+    fn enemy(world: &mut World, health: f32) -> Entity {
+        world
+            .spawn()
+            .insert(Transform::default())
+            .insert(Collider::Enemy)
+            .insert(Enemy {
+                kind: String::new(),
+                speed: 0.0,
+                health,
+            })
+            .id()
+    }
+
+    //a projectile that fires one sub-emitter when it's used up
+    fn fireball(world: &mut World) -> Entity {
+        world
+            .spawn()
+            .insert(Transform::default())
+            .insert(Fireball {
+                origin: Vec3::ZERO,
+                target: Vec3::X,
+                speed: 0.0,
+                damage: 1.0,
+                owner: 0,
+            })
+            .insert(OnDeath(vec![Emitter::default()]))
+            .id()
+    }
+
+    fn hit(world: &mut World, attacker: Entity, victim: Entity) {
+        world
+            .get_resource_mut::<Events<Hit>>()
+            .unwrap()
+            .send(Hit { attacker, victim });
+    }
+
+    fn despawning(world: &mut World) -> Vec<Entity> {
+        let mut marked: Vec<Entity> = world
+            .query_filtered::<Entity, With<Despawning>>()
+            .iter(world)
+            .collect();
+        marked.sort();
+        marked
+    }
+
+    fn sub_emitters(world: &mut World) -> usize {
+        world.query::<&ActiveEmitter>().iter(world).count()
+    }
+
+    fn effects(world: &mut World) -> Vec<Effect> {
+        let events = world.get_resource::<Events<ParticleEvent>>().unwrap();
+        events
+            .get_reader()
+            .iter(events)
+            .map(|ev| ev.effect)
+            .collect()
+    }
+
+    #[test]
+    fn two_fireballs_one_enemy() {
+        let (mut world, mut stage) = setup();
+        let target = enemy(&mut world, 1.0);
+        let first = fireball(&mut world);
+        let second = fireball(&mut world);
+        hit(&mut world, first, target);
+        hit(&mut world, second, target);
+        stage.run(&mut world);
+
+        //the second fireball finds the enemy already dead and keeps going
+        let mut expected = vec![target, first];
+        expected.sort();
+        assert_eq!(despawning(&mut world), expected);
+        assert_eq!(sub_emitters(&mut world), 1);
+        assert_eq!(world.get_resource::<Score>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn pierce_fireball_two_enemies() {
+        let (mut world, mut stage) = setup();
+        let weak = enemy(&mut world, 1.0);
+        let tough = enemy(&mut world, 3.0);
+        let ball = fireball(&mut world);
+        world.entity_mut(ball).insert(Pierce::new(1));
+        //overlapping the same enemy twice in one step only counts once
+        hit(&mut world, ball, weak);
+        hit(&mut world, ball, weak);
+        hit(&mut world, ball, tough);
+        hit(&mut world, ball, tough);
+        stage.run(&mut world);
+
+        let mut expected = vec![weak, ball];
+        expected.sort();
+        assert_eq!(despawning(&mut world), expected);
+        assert_eq!(sub_emitters(&mut world), 1);
+        assert_eq!(world.get_resource::<Score>().unwrap().0, 1);
+        assert_eq!(world.get::<Enemy>(tough).unwrap().health, 2.0);
+    }
+
+    #[test]
+    fn one_effect_per_outcome() {
+        let (mut world, mut stage) = setup();
+        let target = enemy(&mut world, 1.0);
+        let wall = world
+            .spawn()
+            .insert(Transform::default())
+            .insert(Collider::Solid)
+            .id();
+        let first = fireball(&mut world);
+        let second = fireball(&mut world);
+        let third = fireball(&mut world);
+        //both overlap the enemy but only one kills it, the other overlaps it and the wall
+        hit(&mut world, first, target);
+        hit(&mut world, first, target);
+        hit(&mut world, second, target);
+        hit(&mut world, second, wall);
+        hit(&mut world, second, wall);
+        //one that's already spent on the wall doesn't spray again
+        hit(&mut world, third, wall);
+        hit(&mut world, third, target);
+        hit(&mut world, third, wall);
+        stage.run(&mut world);
+
+        let mut effects = effects(&mut world);
+        effects.sort_by_key(|e| format!("{:?}", e));
+        assert_eq!(effects, vec![Effect::Death, Effect::Impact, Effect::Impact]);
+    }
+}
@@ -0,0 +1,276 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    gameplay::Player,
+    movement::Stamina,
+    patterns::{ActiveEmitter, Emitter},
+    status::{StatusDef, Statuses},
+};
+
+//the numbers items can change, each one multiplies whatever it would otherwise be
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    MoveSpeed,
+    Damage,
+    StaminaRegen,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct StatModifier {
+    pub stat: Stat,
+    //added before scaling, as a fraction of the base so 0.1 is +10%
+    #[serde(default)]
+    pub add: f32,
+    #[serde(default = "no_scale")]
+    pub scale: f32,
+}
+
+fn no_scale() -> f32 {
+    1.0
+}
+
+//something an item does when its hook goes off
+#[derive(Deserialize, Clone, Debug)]
+pub enum ItemEffect {
+    RestoreStamina(f32),
+    //put on the enemy that was hit or killed
+    Inflict(StatusDef),
+    //fired from where it happened, towards wherever the shot or hit was going
+    Emit(Emitter),
+}
+
+//what sets off an item's effects
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    Kill,
+    Hit,
+    Shoot,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ItemDef {
+    pub name: String,
+    pub description: String,
+    pub stats: Vec<StatModifier>,
+    pub on_kill: Vec<ItemEffect>,
+    pub on_hit: Vec<ItemEffect>,
+    pub on_shoot: Vec<ItemEffect>,
+}
+
+impl Default for ItemDef {
+    fn default() -> Self {
+        ItemDef {
+            name: "Unnamed".to_string(),
+            description: String::new(),
+            stats: Vec::new(),
+            on_kill: Vec::new(),
+            on_hit: Vec::new(),
+            on_shoot: Vec::new(),
+        }
+    }
+}
+
+impl ItemDef {
+    fn effects(&self, hook: Hook) -> &[ItemEffect] {
+        match hook {
+            Hook::Kill => &self.on_kill,
+            Hook::Hit => &self.on_hit,
+            Hook::Shoot => &self.on_shoot,
+        }
+    }
+}
+
+//every item in the game by id, authored as a .items.ron file in assets/items
+//sorted so picking one with the run's rng always gets the same item
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "2f9a4c61-83d7-4e0b-b5a2-6c1d7e9f3a04"]
+pub struct ItemRegistry {
+    pub items: BTreeMap<String, ItemDef>,
+}
+
+#[derive(Default)]
+pub struct ItemRegistryLoader;
+
+impl AssetLoader for ItemRegistryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let registry = ron::de::from_bytes::<ItemRegistry>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(registry));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items.ron"]
+    }
+}
+
+//--components--//
+
+//the passive items a player has picked up by id, with the stats they add up to
+#[derive(Component, Default)]
+pub struct Inventory {
+    pub items: Vec<String>,
+    pub stats: Stats,
+}
+
+impl Inventory {
+    pub fn with_items(items: Vec<String>) -> Inventory {
+        Inventory {
+            items,
+            ..Default::default()
+        }
+    }
+
+    pub fn refresh(&mut self, registry: &ItemRegistry) {
+        let mut stats = Stats::default();
+        for def in self.defs(registry) {
+            for m in def.stats.iter() {
+                let value = match m.stat {
+                    Stat::MoveSpeed => &mut stats.move_speed,
+                    Stat::Damage => &mut stats.damage,
+                    Stat::StaminaRegen => &mut stats.stamina_regen,
+                };
+                *value = (*value + m.add) * m.scale;
+            }
+        }
+        self.stats = stats;
+    }
+
+    pub fn defs<'a>(&'a self, registry: &'a ItemRegistry) -> impl Iterator<Item = &'a ItemDef> {
+        self.items.iter().filter_map(|id| registry.items.get(id))
+    }
+}
+
+//what each stat is multiplied by once every item is counted
+#[derive(Clone, Copy, Debug)]
+pub struct Stats {
+    pub move_speed: f32,
+    pub damage: f32,
+    pub stamina_regen: f32,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            move_speed: 1.0,
+            damage: 1.0,
+            stamina_regen: 1.0,
+        }
+    }
+}
+
+//--events--//
+
+//something happened that items might care about
+pub struct ItemTrigger {
+    pub hook: Hook,
+    //slot of the player it's down to, or none when nobody in particular did it and everyone's items count
+    pub player: Option<usize>,
+    pub position: Vec3,
+    pub direction: Vec2,
+    //the enemy that was hit or killed
+    pub target: Option<Entity>,
+}
+
+//--resources--//
+
+pub struct Items(pub Handle<ItemRegistry>);
+
+//--systems--//
+
+pub fn log_registry(
+    mut events: EventReader<AssetEvent<ItemRegistry>>,
+    items: Res<Items>,
+    registries: Res<Assets<ItemRegistry>>,
+) {
+    for ev in events.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = ev {
+            match registries.get(handle) {
+                Some(registry) if *handle == items.0 => {
+                    info!("Loaded {} items", registry.items.len())
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+//work stats out from scratch every step, items can be picked up or changed on disk at any time
+//and there are only ever a handful
+pub fn refresh_stats(
+    items: Res<Items>,
+    registries: Res<Assets<ItemRegistry>>,
+    mut q: Query<&mut Inventory>,
+) {
+    let registry = match registries.get(&items.0) {
+        Some(r) => r,
+        None => return,
+    };
+    for mut inventory in q.iter_mut() {
+        inventory.refresh(registry);
+    }
+}
+
+//run every item effect hooked to what happened this step
+pub fn run_hooks(
+    mut commands: Commands,
+    mut triggers: EventReader<ItemTrigger>,
+    items: Res<Items>,
+    registries: Res<Assets<ItemRegistry>>,
+    mut players: Query<(&Player, &Inventory, &mut Stamina)>,
+    mut statuses: Query<&mut Statuses>,
+) {
+    let registry = match registries.get(&items.0) {
+        Some(r) => r,
+        None => return,
+    };
+    for trigger in triggers.iter() {
+        for (player, inventory, mut stamina) in players.iter_mut() {
+            if trigger.player.is_some_and(|slot| slot != player.slot) {
+                continue;
+            }
+            for def in inventory.defs(registry) {
+                for effect in def.effects(trigger.hook) {
+                    match effect {
+                        ItemEffect::RestoreStamina(amount) => stamina.restore(*amount),
+                        ItemEffect::Inflict(status) => {
+                            let target = match trigger.target {
+                                Some(t) => t,
+                                None => continue,
+                            };
+                            match statuses.get_mut(target) {
+                                Ok(mut s) => s.apply(status),
+                                Err(_) => {
+                                    commands
+                                        .entity(target)
+                                        .insert(Statuses::from_defs(std::slice::from_ref(status)));
+                                }
+                            }
+                        }
+                        ItemEffect::Emit(emitter) => {
+                            commands.spawn().insert(ActiveEmitter::new(
+                                emitter.clone(),
+                                trigger.position,
+                                trigger.direction,
+                                player.slot,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1638 @@
+use attacks::Attack;
+use bevy::{
+    app::{ScheduleRunnerPlugin, ScheduleRunnerSettings},
+    asset::AssetServerSettings,
+    audio::{AudioLoader, AudioPlugin},
+    ecs::schedule::ShouldRun,
+    log::{LogPlugin, LogSettings},
+    prelude::*,
+    render::options::WgpuOptions,
+    sprite::collide_aabb::{collide, Collision},
+    transform::TransformSystem,
+    winit::WinitPlugin,
+};
+use bevy_rapier2d::{
+    physics::{self, PhysicsSystems, TimestepMode},
+    prelude::*,
+};
+use config::GameConfig;
+use hits::{Despawning, Hit, Hostile};
+use log::{debug, error, info};
+use net::{NetMode, NetSession, Probe};
+use patterns::{BulletPattern, PatternAttack, PatternLoader};
+use pool::{EntityPool, Pooled};
+use rand::{rngs::StdRng, SeedableRng};
+use replay::{Playback, Recorder, ReplayMode};
+use save::LoadedRun;
+use settings::Settings;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use timestep::FixedUpdate;
+
+mod animation;
+mod attacks;
+mod audio;
+mod bomb;
+mod camera;
+mod config;
+mod damage;
+mod enemies;
+mod gameplay;
+mod hits;
+mod items;
+mod loading;
+mod logging;
+mod menu;
+mod movement;
+mod net;
+mod options;
+mod particles;
+mod patterns;
+mod pickups;
+pub mod pool;
+mod replay;
+mod save;
+mod settings;
+mod sfx;
+mod status;
+mod timestep;
+mod ui;
+
+use gameplay::*;
+
+//tints to tell the players apart, by slot
+const PLAYER_COLORS: [Color; MAX_PLAYERS] = [Color::WHITE, Color::rgb(1.0, 0.7, 0.5)];
+
+//the whole game, main just calls this, the crate is a library as well so the benches can use its parts
+pub fn run() {
+    let args = match config::Args::parse() {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let playback = match args.replay {
+        ReplayMode::Playback(ref path) => match replay::Replay::read(path) {
+            Ok(r) => Some(Playback::new(r)),
+            Err(e) => {
+                eprintln!("Can't play back {}: {:#}", path.display(), e);
+                std::process::exit(2);
+            }
+        },
+        _ => None,
+    };
+    let local = match GameConfig::load(&args) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(2);
+        }
+    };
+
+    //set up logging, see logging::init for when bevy's LogPlugin is used
+    let log_settings = logging::settings(args.log_level);
+    let log_plugin = logging::init(&log_settings, &local.log);
+
+    //playback has to use the tunables the replay was recorded with
+    let config = match playback {
+        Some(ref p) => match p.config().validate() {
+            Ok(()) => p.config(),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                std::process::exit(2);
+            }
+        },
+        None => local,
+    };
+
+    //config files from before the options menu can still ask for fullscreen, until it's saved a choice
+    let mut settings = settings::Settings::load();
+    if config.fullscreen && !Path::new(settings::SETTINGS_PATH).exists() {
+        settings.display = settings::DisplayMode::Fullscreen;
+    }
+    if args.windowed && settings.display == settings::DisplayMode::Fullscreen {
+        settings.display = settings::DisplayMode::Windowed;
+    }
+
+    //a networked run is played with the host's tunables and seed, playback never connects
+    let connected = match (&args.net, &playback) {
+        (NetMode::Off, _) | (_, Some(_)) => Ok(None),
+        (NetMode::Host(port), None) => net::host(*port, &config).map(Some),
+        (NetMode::Join(addr), None) => net::join(addr).map(Some),
+    };
+    let (net, config) = match connected {
+        Ok(Some((session, config))) => (Some(session), config),
+        Ok(None) => (None, config),
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let recorder = match args.replay {
+        ReplayMode::Record(ref path) => Some(Recorder::new(path.clone())),
+        _ => None,
+    };
+    //playback has as many players as the recording did, a networked run always has both
+    let players = match (&net, &playback) {
+        (Some(_), _) => PlayerCount(MAX_PLAYERS),
+        (None, Some(p)) => p.players(),
+        (None, None) => PlayerCount(args.players),
+    };
+    //headless runs go straight into a run without a window, playback always does
+    let headless = args.headless || playback.is_some();
+    //playback goes as fast as it can, anything else keeps to real time
+    let unpaced = playback.is_some();
+
+    let mut app = build_app(
+        config,
+        settings,
+        log_settings,
+        Launch {
+            headless,
+            log_plugin,
+            players,
+            playback,
+            recorder,
+            net,
+            probe: None,
+            unpaced,
+            assets: None,
+        },
+    );
+    let status = app
+        .world
+        .get_resource::<ExitStatus>()
+        .cloned()
+        .unwrap_or_default();
+    //the tests keep what they started with, a normal session follows edits to the config file
+    app.insert_resource(config::ConfigWatch::new(&args)).run();
+    //let scripts tell a session that failed apart from one that went fine
+    if status.code() != 0 {
+        std::process::exit(status.code());
+    }
+}
+
+//how a session is started, everything that doesn't come from the config or settings
+struct Launch {
+    headless: bool,
+    //whether bevy's LogPlugin sets up the console, it can only be done once per process
+    log_plugin: bool,
+    players: PlayerCount,
+    playback: Option<Playback>,
+    recorder: Option<Recorder>,
+    net: Option<NetSession>,
+    probe: Option<Probe>,
+    //step once a frame as fast as frames come instead of keeping to real time
+    unpaced: bool,
+    //load from somewhere other than assets/, the tests use it to leave files out
+    assets: Option<String>,
+}
+
+fn build_app(
+    config: GameConfig,
+    settings: Settings,
+    log_settings: LogSettings,
+    launch: Launch,
+) -> App {
+    let Launch {
+        headless,
+        log_plugin,
+        players,
+        playback,
+        recorder,
+        net,
+        probe,
+        unpaced,
+        assets,
+    } = launch;
+
+    let mut app = App::new();
+    app.insert_resource(WindowDescriptor {
+        title: "Game Thing".to_string(),
+        width: config.window_width,
+        height: config.window_height,
+        mode: settings.display.window_mode(),
+        vsync: settings.vsync,
+        resizable: settings.display.resizable(),
+        ..Default::default()
+    })
+    .insert_resource(log_settings)
+    .init_resource::<MousePos>()
+    .init_resource::<MouseDelta>();
+
+    if let Some(asset_folder) = assets {
+        app.insert_resource(AssetServerSettings { asset_folder });
+    }
+    if let Some(recorder) = recorder {
+        app.insert_resource(recorder);
+    }
+    app.insert_resource(players);
+
+    let mut clock = timestep::FixedClock::default();
+    clock.unpaced = unpaced;
+    if headless {
+        let wait = if clock.unpaced {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f32(timestep::FIXED_STEP)
+        };
+        if let Some(playback) = playback {
+            if let Some(start) = playback.start() {
+                app.insert_resource(start);
+            }
+            app.insert_resource(playback);
+        }
+        app.insert_resource(WgpuOptions {
+            backends: None,
+            ..Default::default()
+        })
+        .insert_resource(ScheduleRunnerSettings::run_loop(wait));
+    }
+    app.insert_resource(clock);
+
+    //a networked run starts as soon as both players are in
+    let in_session = net.is_some();
+    if let Some(mut session) = net {
+        session.headless = headless;
+        app.insert_resource(session);
+    }
+    if let Some(probe) = probe {
+        app.insert_resource(probe);
+    }
+
+    //sound goes through the mixer instead of bevy's AudioPlugin, see audio.rs
+    app.add_plugins_with(DefaultPlugins, |group| {
+        group.disable::<AudioPlugin>();
+        //only ever one global subscriber, LogPlugin's unless logging::init put one in for the log files
+        if !log_plugin {
+            group.disable::<LogPlugin>();
+        }
+        if headless {
+            group.disable::<WinitPlugin>();
+        }
+        group
+    });
+
+    if headless {
+        app.add_plugin(ScheduleRunnerPlugin)
+            .insert_non_send_resource(audio::Mixer::silent());
+    } else {
+        app.init_non_send_resource::<audio::Mixer>();
+    }
+    //everything loads first, then a run starts straight away if there's nobody at a menu to start one
+    app.add_state(GameState::Loading)
+        .insert_resource(loading::AfterLoading {
+            next: if headless || in_session {
+                GameState::Playing
+            } else {
+                GameState::MainMenu
+            },
+            headless,
+        });
+
+    app.add_plugin(timestep::FixedPhysicsPlugin)
+        .add_asset::<BulletPattern>()
+        .init_asset_loader::<PatternLoader>()
+        .add_asset::<AudioSource>()
+        .init_asset_loader::<AudioLoader>()
+        .add_asset::<audio::MusicConfig>()
+        .init_asset_loader::<audio::MusicConfigLoader>()
+        .add_asset::<particles::ParticleEffects>()
+        .init_asset_loader::<particles::ParticleEffectsLoader>()
+        .add_asset::<sfx::SoundBank>()
+        .init_asset_loader::<sfx::SoundBankLoader>()
+        .add_asset::<items::ItemRegistry>()
+        .init_asset_loader::<items::ItemRegistryLoader>()
+        .add_asset::<enemies::EnemyRoster>()
+        .init_asset_loader::<enemies::EnemyRosterLoader>()
+        .insert_resource(ClearColor(Color::rgb(25.0, 25.0, 50.0)))
+        .insert_resource(config)
+        .insert_resource(settings)
+        .init_resource::<options::Rebinding>()
+        .init_resource::<StepTime>()
+        .init_resource::<ExitStatus>()
+        .init_resource::<CosmeticRng>()
+        .init_resource::<PlayerInputs>()
+        .init_resource::<camera::ScreenShake>()
+        .init_resource::<camera::Framing>()
+        .init_resource::<EntityPool<Fireball>>()
+        .init_resource::<EntityPool<Enemy>>()
+        .add_event::<PlayerHitEvent>()
+        .add_event::<Hit>()
+        .add_event::<damage::DamageEvent>()
+        .add_event::<particles::ParticleEvent>()
+        .add_event::<sfx::SoundEvent>()
+        .add_event::<items::ItemTrigger>()
+        .add_event::<animation::AnimationFinished>()
+        //gameplay and physics run here in fixed steps, as many as the frame time calls for
+        .add_stage_after(
+            CoreStage::Update,
+            FixedUpdate,
+            SystemStage::parallel().with_run_criteria(timestep::fixed_step),
+        )
+        //the peer's input has to be in before the fixed stage decides whether to step
+        .add_system_to_stage(CoreStage::PreUpdate, net::pump)
+        .add_startup_system(setup_phys)
+        .add_startup_system(setup)
+        .add_startup_system(watch_assets)
+        .add_system_set(
+            SystemSet::on_enter(GameState::Loading).with_system(loading::spawn_loading_screen),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Loading).with_system(loading::check_loading),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Loading).with_system(loading::despawn_loading_screen),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::MainMenu)
+                .with_system(end_run)
+                .with_system(net::leave)
+                .with_system(replay::finish_recording)
+                .with_system(particles::clear_particles)
+                .with_system(menu::spawn_main_menu),
+        )
+        .add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(menu::menu_buttons))
+        .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(menu::despawn_menu))
+        //the options menu sits on top of whichever menu opened it
+        .add_system_set(SystemSet::on_pause(GameState::MainMenu).with_system(menu::despawn_menu))
+        .add_system_set(
+            SystemSet::on_resume(GameState::MainMenu).with_system(menu::spawn_main_menu),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(start_run)
+                .with_system(replay::begin_run),
+        )
+        //things that only care about the rendered frame
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(mouse_sys)
+                .with_system(animation::animate)
+                .with_system(particles::start_effects)
+                .with_system(particles::run_emitters)
+                .with_system(particles::update_particles)
+                .with_system(damage::spawn_damage_numbers)
+                .with_system(damage::float_damage_numbers)
+                .with_system(damage::flash_hits)
+                .with_system(damage::tint_sprites)
+                .with_system(movement::trail_afterimages)
+                .with_system(movement::fade_afterimages)
+                .with_system(ui::show_hearts)
+                .with_system(ui::settle_hearts)
+                .with_system(ui::show_heart_frames)
+                .with_system(ui::update_meters)
+                .with_system(ui::show_attacks)
+                .with_system(ui::update_run_text)
+                .with_system(bomb::show_bombs)
+                .with_system(menu::pause_input)
+                .with_system(save::save_on_close)
+                .with_system(net::apply_reconcile),
+        )
+        //each step runs strictly input, gameplay, physics, then recording,
+        //so a replay steps through exactly the same order
+        .add_system_set_to_stage(
+            FixedUpdate,
+            SystemSet::new()
+                .with_run_criteria(is_playing)
+                .label(FrameStep::Input)
+                .with_system(timestep::begin_tick)
+                .with_system(replay::step_frame.label(FrameStep::ReadInput))
+                .with_system(
+                    net::drive_probe
+                        .after(FrameStep::ReadInput)
+                        .before(FrameStep::Exchange),
+                )
+                .with_system(
+                    net::exchange_inputs
+                        .label(FrameStep::Exchange)
+                        .after(FrameStep::ReadInput),
+                )
+                .with_system(pool::recycle::<Fireball>)
+                .with_system(pool::recycle::<Enemy>)
+                .with_system(items::refresh_stats),
+        )
+        .add_system_set_to_stage(
+            FixedUpdate,
+            SystemSet::new()
+                .with_run_criteria(is_playing)
+                .label(FrameStep::Gameplay)
+                .after(FrameStep::Input)
+                .with_system(move_sys.label(FrameStep::Movement))
+                .with_system(aim_reticles.label(FrameStep::Aim))
+                .with_system(
+                    spawn_fireball
+                        .after(FrameStep::Aim)
+                        .before(FrameStep::Hooks),
+                )
+                .with_system(patterns::expand_patterns.after(FrameStep::Despawn))
+                .with_system(patterns::run_emitters.after(FrameStep::Despawn))
+                .with_system(move_fireball.label(FrameStep::Movement))
+                .with_system(
+                    spawn_enemies
+                        .label(FrameStep::Spawn)
+                        .after(FrameStep::Despawn),
+                )
+                .with_system(tick_run_time)
+                .with_system(pickups::spawn_pickups.after(FrameStep::Spawn))
+                .with_system(pickups::collect_pickups.before(FrameStep::Hooks))
+                .with_system(bomb::use_bomb.before(FrameStep::Resolve))
+                .with_system(move_enemies.label(FrameStep::Movement))
+                .with_system(
+                    collide_enemies
+                        .label(FrameStep::Collision)
+                        .after(FrameStep::Movement),
+                )
+                .with_system(
+                    collide_player
+                        .label(FrameStep::Hits)
+                        .after(FrameStep::Collision),
+                )
+                .with_system(
+                    collide_fireballs
+                        .label(FrameStep::Hits)
+                        .after(FrameStep::Collision),
+                )
+                .with_system(
+                    hits::resolve_hits
+                        .label(FrameStep::Resolve)
+                        .after(FrameStep::Hits),
+                )
+                .with_system(
+                    status::tick_statuses
+                        .after(FrameStep::Resolve)
+                        .before(FrameStep::Hooks),
+                )
+                .with_system(
+                    items::run_hooks
+                        .label(FrameStep::Hooks)
+                        .after(FrameStep::Resolve),
+                )
+                .with_system(movement::tick_modifiers.after(FrameStep::Resolve))
+                .with_system(movement::tick_invulnerability.after(FrameStep::Resolve))
+                .with_system(ui::player_hit_handler.after(FrameStep::Resolve))
+                .with_system(
+                    hits::despawn_marked
+                        .label(FrameStep::Despawn)
+                        .after(FrameStep::Hooks),
+                ),
+        )
+        .add_system_set_to_stage(
+            FixedUpdate,
+            SystemSet::new()
+                .with_run_criteria(is_playing)
+                .label(FrameStep::Physics)
+                .after(FrameStep::Gameplay)
+                .with_system(
+                    physics::step_world_system::<NoUserData>.label(PhysicsSystems::StepWorld),
+                )
+                .with_system(physics::sync_transforms.after(PhysicsSystems::StepWorld)),
+        )
+        .add_system_set_to_stage(
+            FixedUpdate,
+            SystemSet::new()
+                .with_run_criteria(is_playing)
+                .after(FrameStep::Physics)
+                .with_system(timestep::end_tick.label(FrameStep::EndTick))
+                .with_system(replay::record_frame.after(FrameStep::EndTick))
+                .with_system(replay::check_frame.after(FrameStep::EndTick))
+                .with_system(net::sync_checksums.after(FrameStep::EndTick))
+                .with_system(net::record_probe.after(FrameStep::EndTick)),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            timestep::interpolate.before(TransformSystem::TransformPropagate),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Paused)
+                .with_system(save::save_run)
+                .with_system(menu::spawn_pause_menu),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Paused)
+                .with_system(menu::menu_buttons)
+                .with_system(menu::pause_input),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Paused).with_system(menu::despawn_menu))
+        .add_system_set(SystemSet::on_pause(GameState::Paused).with_system(menu::despawn_menu))
+        .add_system_set(SystemSet::on_resume(GameState::Paused).with_system(menu::spawn_pause_menu))
+        .add_system_set(SystemSet::on_enter(GameState::Options).with_system(options::spawn_options))
+        .add_system_set(
+            SystemSet::on_update(GameState::Options)
+                .with_system(options::option_buttons)
+                .with_system(options::options_keys)
+                .with_system(options::refresh_options),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Options)
+                .with_system(options::despawn_options)
+                .with_system(settings::save_settings),
+        )
+        //settings can change from the options menu at any time
+        .add_system(settings::apply_display)
+        .add_system(config::reload_config)
+        .add_system(camera::frame_players)
+        .add_system(camera::shake_camera)
+        .add_system(audio::load_playlists)
+        .add_system(audio::pick_intensity)
+        .add_system(audio::direct_music)
+        .add_system(sfx::load_clips)
+        .add_system(sfx::play_sfx)
+        .add_system(items::log_registry)
+        .add_system(enemies::apply_roster)
+        .add_system(loading::log_reloads::<Image>)
+        .add_system(loading::log_reloads::<BulletPattern>)
+        .add_system(patterns::refresh_emitters)
+        .add_system(loading::log_reloads::<particles::ParticleEffects>)
+        .add_system(loading::log_reloads::<enemies::EnemyRoster>)
+        .add_system_to_stage(CoreStage::Last, replay::finish_recording_on_exit)
+        .add_system_to_stage(CoreStage::Last, pool::report_on_exit::<Fireball>)
+        .add_system_to_stage(CoreStage::Last, pool::report_on_exit::<Enemy>)
+        .add_system_to_stage(CoreStage::Last, net::leave_on_exit);
+    app
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+enum FrameStep {
+    Input,
+    //the local input is read, then swapped for both players' when networked
+    ReadInput,
+    Exchange,
+    Gameplay,
+    //reticles are moved before anyone fires at them
+    Aim,
+    //order within gameplay, so systems touching the same transforms always run the same way round
+    Movement,
+    Collision,
+    //overlaps are reported, then resolved once each
+    Hits,
+    Resolve,
+    //items react to whatever was hit, killed or fired this step
+    Hooks,
+    //whatever was marked last step goes back to its pool before anything is spawned
+    Despawn,
+    //enemies and pickups both draw from GameRng, always in that order
+    Spawn,
+    Physics,
+    EndTick,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    //waiting on everything in the asset manifest
+    Loading,
+    MainMenu,
+    Playing,
+    Paused,
+    Options,
+}
+
+//state run criteria only work in the stage the state was added to, so the fixed stage checks by hand
+fn is_playing(state: Res<State<GameState>>) -> ShouldRun {
+    if *state.current() == GameState::Playing {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+//--components--//
+
+#[derive(Component)]
+struct MainCamera;
+
+#[derive(Component)]
+pub enum Collider {
+    Solid,
+    Enemy,
+    Projectile,
+}
+
+//Hitbox with a size
+#[derive(Component)]
+pub struct Hitbox(Vec2);
+
+//each player fires on their own timer, paused while they aren't firing
+#[derive(Component)]
+pub struct FireballTimer(Timer);
+
+#[derive(Component)]
+pub struct CurrentAttack(
+    Box<dyn Attack + Send + Sync>, // Box<dyn FnMut(&mut Commands, &Vec3, &Vec3, &Handle<ColorMaterial>) + Send + Sync>,
+);
+
+//--events--//
+//these need to be public for use in other files
+
+//the player that got hit
+pub struct PlayerHitEvent(Entity);
+
+//--resources--//
+
+pub struct FireballSpr(Handle<TextureAtlas>);
+pub struct EnemySpr(Handle<TextureAtlas>);
+#[derive(Default)]
+struct MousePos(Transform);
+#[derive(Default)]
+struct MouseDelta(Vec2);
+
+#[derive(Component)]
+pub struct EnemyTimer(Timer);
+
+pub struct DifficultyTimer(Timer);
+
+//how far gameplay moves this step, always timestep::FIXED_STEP unless a replay recorded otherwise
+#[derive(Default)]
+pub struct StepTime {
+    delta: Duration,
+}
+
+impl StepTime {
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+}
+
+//what the process exits with once the app stops, anything that decides the session failed sets it
+//shared so run can still read it after App::run has taken the app
+#[derive(Clone, Default)]
+pub struct ExitStatus(Arc<AtomicI32>);
+
+impl ExitStatus {
+    pub fn fail(&self, code: i32) {
+        self.0.store(code, Ordering::Relaxed);
+    }
+
+    pub fn code(&self) -> i32 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+//all gameplay randomness goes through this so a run can be replayed from its seed
+pub struct GameRng(pub StdRng);
+
+//randomness that's only for show, particles and screen shake, kept off GameRng so how much of it
+//a frame draws can never change how a run plays out
+pub struct CosmeticRng(pub StdRng);
+
+impl Default for CosmeticRng {
+    fn default() -> Self {
+        CosmeticRng(StdRng::from_entropy())
+    }
+}
+
+//enemies killed this run
+#[derive(Default)]
+pub struct Score(u32);
+
+//goes up every time the difficulty does
+#[derive(Default)]
+pub struct Wave(u32);
+
+//seconds played this run, counted in fixed steps so pauses don't add to it and replays agree
+#[derive(Default)]
+pub struct RunTime(f32);
+
+//--systems--//
+
+//configure the physics world and simulation
+fn setup_phys(config: Res<GameConfig>, mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.scale = config.phys_scale;
+    //dt is set from StepTime every step, see replay::step_frame
+    rapier_config.timestep_mode = TimestepMode::FixedTimestep;
+}
+
+//set up assets and stuff, everything by its name in the manifest
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    asset_settings: Res<AssetServerSettings>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let manifest = loading::AssetManifest::read(&asset_settings.asset_folder);
+    if let Err(ref e) = manifest {
+        error!("{:#}", e);
+    }
+    commands.insert_resource(loading::Loading::new(manifest.as_ref().ok(), &asset_server));
+    let manifest = manifest.unwrap_or_default();
+
+    let fireball = texture_atlases.add(TextureAtlas::from_grid(
+        manifest.load(&asset_server, "fireball"),
+        Vec2::new(32.0, 32.0),
+        1,
+        1,
+    ));
+    let enemy = texture_atlases.add(TextureAtlas::from_grid(
+        manifest.load(&asset_server, "enemy"),
+        Vec2::new(14.0, 16.0),
+        1,
+        1,
+    ));
+
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands.insert_resource(FireballSpr(fireball));
+    commands.insert_resource(EnemySpr(enemy));
+    commands.insert_resource(menu::UiFont(manifest.load(&asset_server, "font")));
+
+    commands.insert_resource(audio::MusicDirector::new(
+        manifest.load(&asset_server, "music"),
+    ));
+    commands.insert_resource(sfx::Sfx::new(manifest.load(&asset_server, "sounds")));
+    commands.insert_resource(items::Items(manifest.load(&asset_server, "items")));
+    commands.insert_resource(enemies::Roster(manifest.load(&asset_server, "enemies")));
+    commands.insert_resource(particles::ParticlePool::new(
+        manifest.load(&asset_server, "particles"),
+    ));
+    commands.insert_resource(manifest);
+    info!("Game start :)");
+}
+
+//spawn the players, spawners and HUD for a new run, or rebuild a saved one
+#[allow(clippy::too_many_arguments)]
+fn start_run(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    players: Res<PlayerCount>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    fire_sp: Res<FireballSpr>,
+    enemy_sp: Res<EnemySpr>,
+    mut projectiles: ResMut<EntityPool<Fireball>>,
+    mut enemies: ResMut<EntityPool<Enemy>>,
+    font: Res<menu::UiFont>,
+    manifest: Res<loading::AssetManifest>,
+    roster: Res<enemies::Roster>,
+    rosters: Res<Assets<enemies::EnemyRoster>>,
+    loaded: Option<Res<LoadedRun>>,
+) {
+    let kerb = texture_atlases.add(TextureAtlas::from_grid(
+        manifest.load(&asset_server, "player"),
+        Vec2::new(32.0, 32.0),
+        1,
+        1,
+    ));
+    let reticle = manifest.load(&asset_server, "reticle");
+    let heart = manifest.load(&asset_server, "heart");
+
+    let spawner = manifest.load(&asset_server, "spawner");
+    let half_size = config.half_size();
+
+    let snapshot = loaded.as_ref().map(|l| &l.0);
+
+    //spawn players, side by side when there's more than one
+    for slot in 0..players.0 {
+        let state = snapshot.and_then(|s| s.players.get(slot));
+        let start = Vec2::new((slot as f32 - (players.0 - 1) as f32 / 2.0) * 64.0, 0.0);
+        let player_pos = state.map_or(start, |s| s.position);
+        let reticle_pos = state.map_or(player_pos + Vec2::new(100.0, 0.0), |s| s.reticle);
+        commands
+            .spawn_bundle(SpriteBundle {
+                texture: reticle.clone(),
+                transform: Transform::from_translation(reticle_pos.extend(0.0)),
+                ..Default::default()
+            })
+            .insert(Reticle(slot));
+
+        let fire_timer = state.map_or_else(
+            || {
+                let mut timer = Timer::from_seconds(config.fire_rate, true);
+                timer.pause();
+                timer.reset();
+                timer
+            },
+            |s| s.fire_timer.to_timer(),
+        );
+        let attack = state.map_or(manifest.path("starting_attack"), |s| &s.attack);
+        let player = commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: kerb.clone(),
+                transform: Transform {
+                    translation: player_pos.extend(0.0),
+                    ..Default::default()
+                },
+                sprite: TextureAtlasSprite {
+                    color: PLAYER_COLORS[slot % PLAYER_COLORS.len()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(animation::player())
+            .insert(Player::new(slot, config.player_speed))
+            .insert(state.map_or_else(ui::PlayerHP::default, |s| ui::PlayerHP(s.hp)))
+            .insert(FireballTimer(fire_timer))
+            .insert(CurrentAttack(Box::new(PatternAttack::load(
+                &asset_server,
+                attack,
+            ))))
+            .insert(state.map_or_else(movement::Dash::default, |s| s.dash.clone()))
+            .insert(state.map_or_else(
+                || movement::Stamina::new(config.stamina_max),
+                |s| s.stamina.clone(),
+            ))
+            .insert(items::Inventory::with_items(
+                state.map_or_else(Vec::new, |s| s.items.clone()),
+            ))
+            .insert(
+                state.map_or_else(movement::VelocityModifiers::default, |s| {
+                    s.modifiers.clone()
+                }),
+            )
+            .insert(timestep::Interpolated::new(player_pos.extend(0.0)))
+            .insert(Hitbox(Vec2::new(32.0, 32.0)))
+            .insert_bundle(RigidBodyBundle {
+                position: (player_pos / config.phys_scale).into(),
+                mass_properties: RigidBodyMassPropsFlags::ROTATION_LOCKED.into(),
+                forces: RigidBodyForces {
+                    gravity_scale: 0f32,
+                    ..Default::default()
+                }
+                .into(),
+                ..Default::default()
+            })
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(16.0 / config.phys_scale, 16.0 / config.phys_scale)
+                    .into(),
+                ..Default::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete)
+            .id();
+        if let Some(left) = state.map(|s| s.invulnerable).filter(|left| *left > 0.0) {
+            commands.entity(player).insert(movement::Invulnerable(left));
+        }
+    }
+
+    commands.insert_resource(DifficultyTimer(
+        snapshot.map_or(Timer::from_seconds(config.difficulty_step, true), |s| {
+            s.difficulty.to_timer()
+        }),
+    ));
+    commands.insert_resource(Score(snapshot.map_or(0, |s| s.score)));
+    commands.insert_resource(Wave(snapshot.map_or(0, |s| s.wave)));
+    commands.insert_resource(RunTime(snapshot.map_or(0.0, |s| s.run_time)));
+
+    //spawners go at the pace of the wave the run is on
+    let roster = rosters.get(&roster.0);
+    let spawn_interval = roster.map_or(config.scaled_spawn_interval(), |r| {
+        r.spawn_interval(&config, snapshot.map_or(0, |s| s.wave))
+    });
+    let mut spawner_transform = Transform::from_scale(Vec3::splat(2.0));
+    let spawner_atlas = TextureAtlas::from_grid(spawner, Vec2::new(22.0, 22.0), 3, 1);
+    let spawner_handle = texture_atlases.add(spawner_atlas);
+    //add spawners
+    for x in -1..2 {
+        for y in -1..2 {
+            if x == 0 || y == 0 {
+                continue;
+            }
+            spawner_transform.translation.x = (half_size.x - 100.0) * x as f32;
+            spawner_transform.translation.y = (half_size.y - 100.0) * y as f32;
+
+            let enemy_timer = snapshot
+                .and_then(|s| {
+                    s.spawners.iter().find(|sp| {
+                        sp.position
+                            .distance(spawner_transform.translation.truncate())
+                            < 1.0
+                    })
+                })
+                .map_or_else(
+                    || Timer::from_seconds(spawn_interval, true),
+                    |sp| sp.timer.to_timer(),
+                );
+
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: spawner_handle.clone(),
+                    transform: spawner_transform,
+                    ..Default::default()
+                })
+                .insert(animation::spawner())
+                .insert(EnemySpawn)
+                .insert(EnemyTimer(enemy_timer))
+                .insert(Collider::Solid)
+                .insert(Hitbox(Vec2::new(32.0, 50.0)))
+                .insert_bundle(RigidBodyBundle {
+                    position: Vec2::new(
+                        spawner_transform.translation.x / config.phys_scale,
+                        spawner_transform.translation.y / config.phys_scale,
+                    )
+                    .into(),
+                    body_type: RigidBodyType::Static.into(),
+                    ..Default::default()
+                })
+                .insert_bundle(ColliderBundle {
+                    shape: ColliderShape::cuboid(
+                        22.0 / config.phys_scale,
+                        22.0 / config.phys_scale,
+                    )
+                    .into(),
+                    ..Default::default()
+                });
+            info!("Added enemy spawn at {}", spawner_transform.translation);
+        }
+    }
+
+    let hearts: Vec<ui::PlayerHP> = (0..players.0)
+        .map(|slot| {
+            snapshot
+                .and_then(|s| s.players.get(slot))
+                .map_or_else(ui::PlayerHP::default, |s| ui::PlayerHP(s.hp))
+        })
+        .collect();
+    ui::spawn_hud(
+        &mut commands,
+        &font.0,
+        &heart,
+        &manifest.load(&asset_server, "fireball"),
+        &hearts,
+        config.max_bombs,
+    );
+    commands.insert_resource(ui::Combo::new(snapshot.map_or(0, |s| s.score)));
+    commands.insert_resource(bomb::Bombs(
+        snapshot.map_or(config.starting_bombs, |s| s.bombs),
+    ));
+    commands.insert_resource(pickups::PickupTimers {
+        bomb: snapshot.map_or(
+            Timer::from_seconds(config.bomb_pickup_interval, true),
+            |s| s.bomb_pickup.to_timer(),
+        ),
+        item: snapshot.map_or(
+            Timer::from_seconds(config.item_pickup_interval, true),
+            |s| s.item_pickup.to_timer(),
+        ),
+    });
+
+    if let Some(snapshot) = snapshot {
+        for enemy in snapshot.enemies.iter() {
+            enemy.spawn(&mut commands, &mut enemies, &enemy_sp.0, roster);
+        }
+        for ball in snapshot.projectiles.iter() {
+            ball.spawn(&mut commands, &mut projectiles, &fire_sp.0);
+        }
+        for emitter in snapshot.emitters.iter() {
+            commands.spawn().insert(emitter.clone());
+        }
+        for pattern in snapshot.patterns.iter() {
+            pattern.fire(&mut commands, &asset_server);
+        }
+        for pickup in snapshot.pickups.iter() {
+            pickup.spawn(&mut commands);
+        }
+        commands.remove_resource::<LoadedRun>();
+        info!("Restored run at wave {}", snapshot.wave);
+    }
+}
+
+//clear out everything left over from a run when going back to the menu
+#[allow(clippy::type_complexity)]
+fn end_run(
+    mut commands: Commands,
+    q: Query<
+        Entity,
+        Or<(
+            With<Player>,
+            With<Reticle>,
+            With<EnemySpawn>,
+            With<Pooled>,
+            With<patterns::ActiveEmitter>,
+            With<patterns::PendingPattern>,
+            With<damage::DamageNumber>,
+            With<movement::Afterimage>,
+            With<pickups::Pickup>,
+        )>,
+    >,
+    hud: Query<Entity, With<ui::Hud>>,
+    mut projectiles: ResMut<EntityPool<Fireball>>,
+    mut enemies: ResMut<EntityPool<Enemy>>,
+) {
+    for ent in q.iter() {
+        commands.entity(ent).despawn();
+    }
+    //takes every heart, meter, icon and label with it
+    for ent in hud.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+    projectiles.report();
+    enemies.report();
+    projectiles.clear();
+    enemies.clear();
+}
+
+fn mouse_sys(
+    mut ev_cursor: EventReader<CursorMoved>,
+    mut wnds: ResMut<Windows>,
+    mut pos: ResMut<MousePos>,
+    mut delta: ResMut<MouseDelta>,
+    q_camera: Query<&Transform, With<MainCamera>>,
+    player: Query<(&Transform, &gameplay::Player)>,
+) {
+    //the mouse belongs to whoever is on the keyboard
+    let (camera_transform, player_pos) = match (
+        q_camera.iter().next(),
+        player.iter().find(|(_, p)| p.slot == 0),
+    ) {
+        (Some(camera), Some((player, _))) => (camera, player.translation),
+        _ => return,
+    };
+    let start = pos.0;
+    for ev in ev_cursor.iter() {
+        let wnd = match wnds.get_mut(ev.id) {
+            Some(w) => w,
+            None => continue,
+        };
+
+        let custom_size = Vec2::new(wnd.width(), wnd.height());
+
+        let p = ev.position - custom_size / 2.0;
+
+        //convert the screen coords to world coords
+        let pos_wld = camera_transform.compute_matrix() * p.extend(0.0).extend(1.0);
+
+        let translation = &mut pos.0.translation;
+
+        translation.x = pos_wld.x + player_pos.x;
+        translation.y = pos_wld.y + player_pos.y;
+
+        let res = pos.0.translation - start.translation;
+
+        delta.0 = Vec2::new(delta.0.x + res.x, delta.0.y + res.y);
+    }
+}
+
+//move enemies towards the nearest player that's still alive, or the nearest one at all once none are
+fn move_enemies(
+    time: Res<StepTime>,
+    player_query: Query<(&Transform, &ui::PlayerHP), With<Player>>,
+    mut enemies: Query<(&mut Transform, &Enemy, Option<&status::Statuses>), Without<Player>>,
+) {
+    let anyone_alive = player_query.iter().any(|(_, hp)| hp.0 > 0);
+    let targets: Vec<Vec3> = player_query
+        .iter()
+        .filter(|(_, hp)| hp.0 > 0 || !anyone_alive)
+        .map(|(tr, _)| tr.translation)
+        .collect();
+
+    for (mut transform, enemy, statuses) in enemies.iter_mut() {
+        let here = transform.translation;
+        let target = match targets.iter().min_by(|a, b| {
+            a.distance_squared(here)
+                .total_cmp(&b.distance_squared(here))
+        }) {
+            Some(t) => *t,
+            None => return,
+        };
+        let move_vec = (target - here).normalize_or_zero();
+        let speed = enemy.speed * statuses.map_or(1.0, |s| s.speed_scale());
+
+        transform.translation.x += move_vec.x * speed * time.delta_seconds();
+        transform.translation.y += move_vec.y * speed * time.delta_seconds();
+    }
+}
+
+//move active fireballs towards their target and despawn any that go off screen
+fn move_fireball(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    time: Res<StepTime>,
+    mut q: Query<(Entity, &Fireball, &mut Transform), Without<Despawning>>,
+) {
+    let half_size = config.half_size();
+    for (e, f, mut current) in q.iter_mut() {
+        current.rotate(Quat::from_rotation_z(0.5));
+        let translation = &mut current.translation;
+        let direction = (f.target - f.origin).normalize();
+        translation.x += f.speed * direction.x * time.delta_seconds();
+        translation.y += f.speed * direction.y * time.delta_seconds();
+        //if the fireball goes off screen, remove it
+        if translation.x >= half_size.x + 100.0
+            || translation.x <= -half_size.x - 100.0
+            || translation.y >= half_size.y + 100.0
+            || translation.y <= -half_size.y - 100.0
+        {
+            commands.entity(e).insert(Despawning);
+            debug!("Removed fireball")
+        }
+    }
+}
+
+//--collision systems--//
+
+//report enemies touching a player that's still alive, hits::resolve_hits decides what that does
+//spawners are rapier colliders, so they already keep the players out without any help here
+#[allow(clippy::type_complexity)]
+fn collide_player(
+    players: Query<(Entity, &Transform, &Hitbox, &ui::PlayerHP), With<Player>>,
+    enemies: Query<(Entity, &Transform, &Hitbox), (With<Enemy>, Without<Despawning>)>,
+    mut hits: EventWriter<Hit>,
+) {
+    for (player_ent, player_tr, size, hp) in players.iter() {
+        if hp.0 <= 0 {
+            continue;
+        }
+        for (ent, tr, other_size) in enemies.iter() {
+            if collide(player_tr.translation, size.0, tr.translation, other_size.0).is_some() {
+                hits.send(Hit {
+                    attacker: ent,
+                    victim: player_ent,
+                });
+            }
+        }
+    }
+}
+
+fn collide_enemies(mut q: Query<(&mut Transform, &Hitbox), With<Enemy>>) {
+    let mut enemies = q.iter_mut();
+    while let Some((mut tr, size)) = enemies.next() {
+        if let Some((other_tr, other_size)) = enemies.next() {
+            let collision = collide(tr.translation, size.0, other_tr.translation, other_size.0);
+
+            if let Some(coll) = collision {
+                match coll {
+                    Collision::Top => {
+                        tr.translation.y -= (tr.translation.y - (size.0.y * 0.5))
+                            - (other_tr.translation.y + (size.0.y * 0.5));
+                        tr.translation.y = tr.translation.y.ceil();
+                    }
+                    Collision::Bottom => {
+                        tr.translation.y -= (tr.translation.y + (size.0.y * 0.5))
+                            - (other_tr.translation.y - (size.0.y * 0.5));
+                        tr.translation.y = tr.translation.y.ceil();
+                    }
+                    Collision::Left => {
+                        tr.translation.x -= (tr.translation.x + (size.0.x * 0.5))
+                            - (other_tr.translation.x - (size.0.x * 0.5));
+                        tr.translation.x = tr.translation.x.ceil();
+                    }
+                    Collision::Right => {
+                        tr.translation.x -= (tr.translation.x - (size.0.x * 0.5))
+                            - (other_tr.translation.x + (size.0.x * 0.5));
+                        tr.translation.x = tr.translation.x.ceil();
+                    }
+                }
+            }
+        }
+    }
+}
+
+//report every overlap, hits::resolve_hits decides what actually happens
+#[allow(clippy::type_complexity)]
+fn collide_fireballs(
+    balls: Query<
+        (Entity, &Transform, &Hitbox, Option<&Hostile>),
+        (With<Fireball>, Without<Despawning>),
+    >,
+    col_query: Query<
+        (Entity, &Transform, &Collider, &Hitbox),
+        (Without<Player>, Without<Despawning>),
+    >,
+    player: Query<(Entity, &Transform, &Hitbox, &ui::PlayerHP), With<Player>>,
+    mut hits: EventWriter<Hit>,
+) {
+    for (ball_ent, ball_tr, ball_size, hostile) in balls.iter() {
+        //enemy fire only cares about the players still alive
+        if hostile.is_some() {
+            for (ent, tr, size, _) in player.iter().filter(|(.., hp)| hp.0 > 0) {
+                if collide(ball_tr.translation, ball_size.0, tr.translation, size.0).is_some() {
+                    hits.send(Hit {
+                        attacker: ball_ent,
+                        victim: ent,
+                    });
+                }
+            }
+            continue;
+        }
+        for (ent, tr, col, size) in col_query.iter() {
+            if let Collider::Projectile = *col {
+                continue;
+            }
+            if collide(ball_tr.translation, ball_size.0, tr.translation, size.0).is_some() {
+                hits.send(Hit {
+                    attacker: ball_ent,
+                    victim: ent,
+                });
+            }
+        }
+    }
+}
+
+//reload pattern files and sprites when they change on disk
+fn watch_assets(asset_server: Res<AssetServer>) {
+    if let Err(e) = asset_server.watch_for_changes() {
+        info!("Asset hot reload unavailable: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        app::Events,
+        ecs::{schedule::IntoSystemDescriptor, system::SystemState},
+        transform::hierarchy::despawn_with_children_recursive,
+    };
+    use net::ProbeReport;
+    use save::{RunSnapshot, RunState};
+    use std::{
+        net::UdpSocket,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    //step a headless run until it's past loading and playing
+    fn start_playing(app: &mut App) {
+        let started = std::time::Instant::now();
+        while app
+            .world
+            .get_resource::<State<GameState>>()
+            .unwrap()
+            .current()
+            != &GameState::Playing
+        {
+            assert!(
+                started.elapsed() < Duration::from_secs(30),
+                "the run never finished loading"
+            );
+            app.update();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    //take away the players, camera and reticles partway into a run and carry on,
+    //so anything that assumes they're there panics
+    #[test]
+    fn run_goes_on_without_players() {
+        let mut app = build_app(
+            GameConfig {
+                seed: Some(3),
+                ..Default::default()
+            },
+            Settings::default(),
+            logging::settings(None),
+            Launch {
+                headless: true,
+                log_plugin: false,
+                players: PlayerCount(MAX_PLAYERS),
+                playback: None,
+                recorder: None,
+                net: None,
+                probe: None,
+                unpaced: true,
+                assets: None,
+            },
+        );
+        start_playing(&mut app);
+        for _ in 0..60 {
+            app.update();
+        }
+
+        let mut targets = app
+            .world
+            .query_filtered::<Entity, Or<(With<Player>, With<Reticle>, With<MainCamera>)>>();
+        let gone: Vec<Entity> = targets.iter(&app.world).collect();
+        assert_eq!(gone.len(), MAX_PLAYERS * 2 + 1);
+        for ent in gone.iter() {
+            despawn_with_children_recursive(&mut app.world, *ent);
+        }
+        for _ in 0..120 {
+            app.update();
+        }
+
+        assert_eq!(targets.iter(&app.world).count(), 0);
+        assert!(gone.iter().all(|ent| app.world.get_entity(*ent).is_none()));
+        assert_eq!(
+            app.world
+                .get_resource::<State<GameState>>()
+                .unwrap()
+                .current(),
+            &GameState::Playing
+        );
+    }
+
+    //everything a headless test run needs that isn't the config
+    fn headless(players: usize) -> Launch {
+        Launch {
+            headless: true,
+            log_plugin: false,
+            players: PlayerCount(players),
+            playback: None,
+            recorder: None,
+            net: None,
+            probe: None,
+            unpaced: true,
+            assets: None,
+        }
+    }
+
+    //copy a directory and everything under it
+    fn copy_dir(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &target);
+            } else {
+                std::fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    //sounds and music aren't required, a run without some of them still starts and plays on quietly
+    #[test]
+    fn missing_audio_still_plays() {
+        let assets = std::env::temp_dir().join(format!("game_thing_assets_{}", std::process::id()));
+        copy_dir(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"),
+            &assets,
+        );
+        std::fs::remove_file(assets.join("audio/shoot.mp3")).unwrap();
+        std::fs::remove_file(assets.join("music1.mp3")).unwrap();
+
+        let mut app = build_app(
+            GameConfig {
+                seed: Some(5),
+                ..Default::default()
+            },
+            Settings::default(),
+            logging::settings(None),
+            Launch {
+                assets: Some(assets.to_string_lossy().into_owned()),
+                ..headless(1)
+            },
+        );
+        start_playing(&mut app);
+        //give the missing files time to fail, asking for the sound every frame
+        for _ in 0..120 {
+            app.world
+                .get_resource_mut::<Events<sfx::SoundEvent>>()
+                .unwrap()
+                .send(sfx::SoundEvent::Shoot);
+            app.update();
+            thread::sleep(Duration::from_millis(2));
+        }
+        std::fs::remove_dir_all(&assets).unwrap();
+
+        let server = app.world.get_resource::<AssetServer>().unwrap();
+        assert_eq!(
+            server.get_load_state("audio/shoot.mp3"),
+            bevy::asset::LoadState::Failed
+        );
+        assert_eq!(
+            app.world
+                .get_resource::<State<GameState>>()
+                .unwrap()
+                .current(),
+            &GameState::Playing
+        );
+        assert_eq!(app.world.get_resource::<ExitStatus>().unwrap().code(), 0);
+    }
+
+    //run one system on its own, commands and all
+    fn run_system<Params>(world: &mut World, system: impl IntoSystemDescriptor<Params>) {
+        SystemStage::single(system).run(world);
+    }
+
+    fn snapshot_of(world: &mut World) -> RunSnapshot {
+        let mut run: SystemState<RunState> = SystemState::new(world);
+        let snapshot = run.get_mut(world).snapshot();
+        snapshot.expect("there's no run to snapshot")
+    }
+
+    //the same snapshot whatever order the world's queries gave its entities in
+    fn in_order(mut snapshot: RunSnapshot) -> RunSnapshot {
+        fn sort<T: std::fmt::Debug>(items: &mut [T]) {
+            items.sort_by_cached_key(|item| format!("{:?}", item));
+        }
+        sort(&mut snapshot.spawners);
+        sort(&mut snapshot.enemies);
+        sort(&mut snapshot.projectiles);
+        sort(&mut snapshot.emitters);
+        sort(&mut snapshot.patterns);
+        sort(&mut snapshot.pickups);
+        snapshot
+    }
+
+    //a run saved partway through and continued from the save in another session is the run it left
+    #[test]
+    fn saves_restore_the_run() {
+        let config = GameConfig {
+            seed: Some(13),
+            ..Default::default()
+        };
+        let report = Arc::new(Mutex::new(ProbeReport::default()));
+        let mut played = build_app(
+            config.clone(),
+            Settings::default(),
+            logging::settings(None),
+            Launch {
+                probe: Some(Probe::new(300, None, report)),
+                ..headless(1)
+            },
+        );
+        start_playing(&mut played);
+        for _ in 0..300 {
+            played.update();
+        }
+        let saved = snapshot_of(&mut played.world);
+        assert!(!saved.enemies.is_empty(), "nothing happened in the run");
+
+        let path =
+            std::env::temp_dir().join(format!("game_thing_restore_{}.ron", std::process::id()));
+        save::write_snapshot(&saved, &path).unwrap();
+        let read = save::read_snapshot(&path);
+        std::fs::remove_file(&path).ok();
+
+        //continue it the way the main menu does, over whatever run the new session started with
+        let mut restored = build_app(
+            config,
+            Settings::default(),
+            logging::settings(None),
+            headless(1),
+        );
+        start_playing(&mut restored);
+        restored.world.insert_resource(LoadedRun(read.unwrap()));
+        run_system(&mut restored.world, end_run);
+        run_system(&mut restored.world, start_run);
+
+        assert!(restored.world.get_resource::<LoadedRun>().is_none());
+        assert_eq!(in_order(snapshot_of(&mut restored.world)), in_order(saved));
+    }
+
+    //a headless copy of the game playing a run with scripted input, one end of a session if it's given one
+    fn play(session: Option<NetSession>, config: GameConfig, probe: Probe) {
+        let players = match session {
+            Some(_) => MAX_PLAYERS,
+            None => 1,
+        };
+        build_app(
+            config,
+            Settings::default(),
+            logging::settings(None),
+            Launch {
+                headless: true,
+                log_plugin: false,
+                players: PlayerCount(players),
+                playback: None,
+                recorder: None,
+                net: session,
+                probe: Some(probe),
+                unpaced: true,
+                assets: None,
+            },
+        )
+        .run();
+    }
+
+    //the same seed and inputs always play out the same, step for step
+    #[test]
+    fn runs_are_deterministic() {
+        let frames = 400;
+        let config = GameConfig {
+            seed: Some(11),
+            ..Default::default()
+        };
+        let runs: Vec<Vec<u64>> = (0..2)
+            .map(|_| {
+                let report = Arc::new(Mutex::new(ProbeReport::default()));
+                play(
+                    None,
+                    config.clone(),
+                    Probe::new(frames, None, report.clone()),
+                );
+                let sums = report.lock().unwrap().sums.clone();
+                sums
+            })
+            .collect();
+
+        assert_eq!(runs[0].len(), frames as usize);
+        //something actually happened over the run
+        assert_ne!(runs[0].first(), runs[0].last());
+        if let Some(f) = (0..frames as usize).find(|&f| runs[0][f] != runs[1][f]) {
+            panic!("the second run diverged from the first at frame {}", f);
+        }
+    }
+
+    //play a replay through to the end headlessly, returning what the session would exit with
+    fn play_back(replay: replay::Replay) -> i32 {
+        let playback = Playback::new(replay);
+        let mut app = build_app(
+            playback.config(),
+            Settings::default(),
+            logging::settings(None),
+            Launch {
+                players: playback.players(),
+                playback: Some(playback),
+                ..headless(1)
+            },
+        );
+        let status = app.world.get_resource::<ExitStatus>().cloned().unwrap();
+        app.run();
+        status.code()
+    }
+
+    //a recorded run read back from disk plays out exactly as it was recorded,
+    //and a recording that doesn't match what playback sees fails the session
+    #[test]
+    fn replays_play_back() {
+        let frames = 240;
+        let path =
+            std::env::temp_dir().join(format!("game_thing_replay_{}.ron", std::process::id()));
+        let report = Arc::new(Mutex::new(ProbeReport::default()));
+        build_app(
+            GameConfig {
+                seed: Some(17),
+                ..Default::default()
+            },
+            Settings::default(),
+            logging::settings(None),
+            Launch {
+                recorder: Some(Recorder::new(path.clone())),
+                probe: Some(Probe::new(frames, None, report)),
+                ..headless(1)
+            },
+        )
+        .run();
+
+        let recorded = replay::Replay::read(&path);
+        std::fs::remove_file(&path).ok();
+        let recorded = recorded.unwrap();
+        assert_eq!(recorded.seed, 17);
+        assert_eq!(recorded.frames.len(), frames as usize);
+        assert_eq!(play_back(recorded.clone()), 0);
+
+        let mut tampered = recorded;
+        tampered.frames[frames as usize / 2].checksum ^= 1;
+        assert_eq!(play_back(tampered), 1);
+    }
+
+    //play a session between two copies of the game over localhost, knocking the joining end out of step
+    //partway in, both ends have to come out the same once the reconcile has settled them
+    #[test]
+    fn net_loopback() {
+        let frames = 600;
+        let perturb_at = frames / 3;
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let config = GameConfig {
+            seed: Some(7),
+            ..Default::default()
+        };
+        let reports = [
+            Arc::new(Mutex::new(ProbeReport::default())),
+            Arc::new(Mutex::new(ProbeReport::default())),
+        ];
+
+        let report = reports[0].clone();
+        let host = thread::spawn(move || {
+            let (session, config) = net::accept(socket, &config).unwrap();
+            play(Some(session), config, Probe::new(frames, None, report));
+        });
+        let report = reports[1].clone();
+        let client = thread::spawn(move || {
+            let (session, config) = net::join(&addr.to_string()).unwrap();
+            play(
+                Some(session),
+                config,
+                Probe::new(frames, Some(perturb_at), report),
+            );
+        });
+        host.join().expect("the host panicked");
+        client.join().expect("the client panicked");
+
+        let [host, client] = reports.map(|r| r.lock().unwrap().clone());
+        let frames = frames as usize;
+        assert!(
+            host.sums.len() >= frames && client.sums.len() >= frames,
+            "session ended early, the host stepped {} frames and the client {}",
+            host.sums.len(),
+            client.sums.len()
+        );
+        let reconciled = client
+            .reconciled
+            .expect("the client was knocked out of step but never reconciled")
+            as usize;
+        assert!(reconciled >= perturb_at as usize);
+        if let Some(f) = (reconciled + 1..frames).find(|&f| host.sums[f] != client.sums[f]) {
+            panic!(
+                "host and client diverged at frame {} after reconciling at frame {}",
+                f, reconciled
+            );
+        }
+    }
+}
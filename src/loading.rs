@@ -0,0 +1,233 @@
+use anyhow::Context;
+use bevy::{
+    app::AppExit,
+    asset::{Asset, FileAssetIo, LoadState},
+    prelude::*,
+};
+use log::{error, info};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs};
+
+use crate::{menu::UiFont, ExitStatus, GameState};
+
+pub const MANIFEST_PATH: &str = "game.manifest.ron";
+
+//every file the game can't do without, by the name the code asks for it by
+//nothing past the loading screen runs until they're all in
+#[derive(Deserialize, Default)]
+pub struct AssetManifest {
+    pub files: BTreeMap<String, String>,
+}
+
+impl AssetManifest {
+    //read straight off disk at startup, so everything can get its handles from it right away
+    pub fn read(asset_folder: &str) -> anyhow::Result<AssetManifest> {
+        let path = FileAssetIo::get_root_path()
+            .join(asset_folder)
+            .join(MANIFEST_PATH);
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        ron::from_str(&text).with_context(|| format!("{} is not a valid manifest", path.display()))
+    }
+
+    //where the file with this name lives, a name the manifest doesn't have is used as is so it shows up as missing
+    pub fn path<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.files.get(name) {
+            Some(path) => path,
+            None => {
+                error!("{} has no file called {}", MANIFEST_PATH, name);
+                name
+            }
+        }
+    }
+
+    pub fn load<T: Asset>(&self, asset_server: &AssetServer, name: &str) -> Handle<T> {
+        asset_server.load(self.path(name))
+    }
+}
+
+//--components--//
+
+#[derive(Component)]
+pub struct LoadingScreen;
+
+#[derive(Component)]
+pub struct LoadingText;
+
+//--resources--//
+
+//everything in the manifest, the handles are kept so nothing gets unloaded once it's in
+//with no manifest there's nothing to wait on and loading fails straight away
+pub struct Loading {
+    files: Option<Vec<(String, HandleUntyped)>>,
+    failed: bool,
+}
+
+impl Loading {
+    pub fn new(manifest: Option<&AssetManifest>, asset_server: &AssetServer) -> Loading {
+        let files = manifest.map(|manifest| {
+            manifest
+                .files
+                .values()
+                .map(|path| (path.clone(), asset_server.load_untyped(path.as_str())))
+                .collect()
+        });
+        Loading {
+            files,
+            failed: false,
+        }
+    }
+}
+
+//where to go once loading is done, and whether to quit if it can't be
+pub struct AfterLoading {
+    pub next: GameState,
+    pub headless: bool,
+}
+
+//--systems--//
+
+pub fn spawn_loading_screen(mut commands: Commands, font: Res<UiFont>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(LoadingScreen)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Loading",
+                        TextStyle {
+                            font: font.0.clone(),
+                            font_size: 32.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(LoadingText);
+        });
+}
+
+pub fn despawn_loading_screen(mut commands: Commands, q: Query<Entity, With<LoadingScreen>>) {
+    for ent in q.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
+
+//move on once everything in the manifest is in
+pub fn check_loading(
+    mut loading: ResMut<Loading>,
+    after: Res<AfterLoading>,
+    asset_server: Res<AssetServer>,
+    mut state: ResMut<State<GameState>>,
+    mut text: Query<&mut Text, With<LoadingText>>,
+    status: Res<ExitStatus>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if loading.failed {
+        return;
+    }
+
+    let files = match loading.files {
+        Some(ref f) => f,
+        None => {
+            let missing = [MANIFEST_PATH.to_string()];
+            fail(
+                &mut loading,
+                &after,
+                &missing,
+                &mut text,
+                &status,
+                &mut exit,
+            );
+            return;
+        }
+    };
+
+    let mut missing = Vec::new();
+    let mut loaded = 0;
+    for (path, handle) in files.iter() {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => missing.push(path.clone()),
+            _ => (),
+        }
+    }
+    let total = files.len();
+    if !missing.is_empty() {
+        fail(
+            &mut loading,
+            &after,
+            &missing,
+            &mut text,
+            &status,
+            &mut exit,
+        );
+        return;
+    }
+    if loaded < total {
+        for mut text in text.iter_mut() {
+            if let Some(section) = text.sections.first_mut() {
+                section.value = format!("Loading {}/{}", loaded, total);
+            }
+        }
+        return;
+    }
+
+    info!("Loaded all {} files in {}", total, MANIFEST_PATH);
+    if let Err(e) = state.set(after.next) {
+        error!("Leaving the loading screen failed: {:?}", e);
+    }
+}
+
+//say exactly what's missing, on screen and in the log, there's no playing without it
+fn fail(
+    loading: &mut Loading,
+    after: &AfterLoading,
+    missing: &[String],
+    text: &mut Query<&mut Text, With<LoadingText>>,
+    status: &ExitStatus,
+    exit: &mut EventWriter<AppExit>,
+) {
+    loading.failed = true;
+    error!(
+        "Can't start, {} required file(s) are missing or broken:\n  {}",
+        missing.len(),
+        missing.join("\n  ")
+    );
+    for mut text in text.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = format!("Missing files:\n{}", missing.join("\n"));
+            section.style.color = Color::rgb(1.0, 0.4, 0.4);
+        }
+    }
+    //nobody's watching a headless run, so don't leave it sat there
+    if after.headless {
+        status.fail(2);
+        exit.send(AppExit);
+    }
+}
+
+//say when a file was picked up again after being edited on disk, so it's clear the change took
+pub fn log_reloads<T: Asset>(
+    mut events: EventReader<AssetEvent<T>>,
+    asset_server: Res<AssetServer>,
+) {
+    for ev in events.iter() {
+        if let AssetEvent::Modified { handle } = ev {
+            if let Some(path) = asset_server.get_handle_path(handle) {
+                info!("Reloaded {}", path.path().display());
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use bevy::log::{Level, LogSettings};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[cfg(feature = "file-log")]
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+#[cfg(feature = "file-log")]
+use tracing_subscriber::{
+    filter::Targets,
+    fmt::{self, MakeWriter},
+    prelude::*,
+    registry::Registry,
+    util::SubscriberInitExt,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    //where the dated game and debug logs are written
+    pub dir: PathBuf,
+    //start a new file once the current one gets this big, in kilobytes
+    pub max_size_kb: u64,
+    //how many files of each log are kept around, the oldest go first
+    pub keep: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            dir: "logs".into(),
+            max_size_kb: 5 * 1024,
+            keep: 10,
+        }
+    }
+}
+
+//the same settings bevy's LogPlugin reads, so the console looks the same either way
+pub fn settings(level: Option<Level>) -> LogSettings {
+    LogSettings {
+        level: level.unwrap_or(Level::INFO),
+        ..Default::default()
+    }
+}
+
+//set up the log files, returns whether bevy's LogPlugin still has to set up the console
+//LogPlugin has no way to add layers to the subscriber it installs, and there's only one per process,
+//so while the files are open the console comes from here instead, the way LogPlugin would set it up.
+//when they can't be opened LogPlugin does the console as usual
+#[cfg(feature = "file-log")]
+pub fn init(settings: &LogSettings, config: &LogConfig) -> bool {
+    let files = match open_files(config) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("File logging unavailable: {:#}", e);
+            return true;
+        }
+    };
+    match init_subscriber(settings, files) {
+        Ok(()) => false,
+        Err(e) => {
+            eprintln!("File logging unavailable: {:#}", e);
+            true
+        }
+    }
+}
+
+//without file-log bevy's LogPlugin does it all, from the LogSettings resource
+#[cfg(not(feature = "file-log"))]
+pub fn init(_: &LogSettings, _: &LogConfig) -> bool {
+    true
+}
+
+//console and game log show what LogSettings asks for, or RUST_LOG when it's set like LogPlugin does,
+//the debug log gets everything this crate says on top
+#[cfg(feature = "file-log")]
+fn init_subscriber(
+    settings: &LogSettings,
+    (game, debug): (LogFile, LogFile),
+) -> anyhow::Result<()> {
+    let filter: Targets = match std::env::var("RUST_LOG").ok().and_then(|f| f.parse().ok()) {
+        Some(f) => f,
+        None => format!("{},{}", settings.level, settings.filter).parse()?,
+    };
+    let debug_filter = filter.clone().with_target("game_thing", Level::TRACE);
+
+    Registry::default()
+        .with(fmt::layer().with_filter(filter.clone()))
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(SharedLog::new(game))
+                .with_filter(filter),
+        )
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(SharedLog::new(debug))
+                .with_filter(debug_filter),
+        )
+        .try_init()?;
+    Ok(())
+}
+
+#[cfg(feature = "file-log")]
+fn open_files(config: &LogConfig) -> io::Result<(LogFile, LogFile)> {
+    fs::create_dir_all(&config.dir)?;
+    Ok((
+        LogFile::open(config, "game")?,
+        LogFile::open(config, "debug")?,
+    ))
+}
+
+#[cfg(feature = "file-log")]
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+//a log that starts a new file every day and whenever it gets too big, keeping the last few
+#[cfg(feature = "file-log")]
+struct LogFile {
+    dir: PathBuf,
+    name: &'static str,
+    max_size: u64,
+    keep: usize,
+    date: String,
+    index: u32,
+    file: File,
+    size: u64,
+}
+
+#[cfg(feature = "file-log")]
+impl LogFile {
+    fn open(config: &LogConfig, name: &'static str) -> io::Result<LogFile> {
+        let date = today();
+        //every session starts its own file after whatever today already has
+        let index = existing(&config.dir, name)?
+            .iter()
+            .filter_map(|path| {
+                let stem = path.file_stem()?.to_str()?;
+                stem.strip_prefix(&format!("{}_{}_", name, date))?
+                    .parse()
+                    .ok()
+            })
+            .max()
+            .map_or(0, |i: u32| i + 1);
+
+        let path = log_path(&config.dir, name, &date, index);
+        let log = LogFile {
+            dir: config.dir.clone(),
+            name,
+            max_size: config.max_size_kb * 1024,
+            keep: config.keep.max(1),
+            date,
+            index,
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+            size: 0,
+        };
+        log.prune()?;
+        Ok(log)
+    }
+
+    //a whole record at a time, so one never gets split across two files
+    fn append(&mut self, record: &[u8]) -> io::Result<()> {
+        self.rotate(record.len() as u64)?;
+        self.file.write_all(record)?;
+        self.size += record.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self, incoming: u64) -> io::Result<()> {
+        let date = today();
+        if date != self.date {
+            self.date = date;
+            self.index = 0;
+        } else if self.size > 0 && self.size + incoming > self.max_size {
+            self.index += 1;
+        } else {
+            return Ok(());
+        }
+
+        let path = log_path(&self.dir, self.name, &self.date, self.index);
+        self.file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.size = 0;
+        self.prune()
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        let files = existing(&self.dir, self.name)?;
+        for old in files.iter().take(files.len().saturating_sub(self.keep)) {
+            fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+}
+
+//hands the fmt layer a fresh buffer per event, which goes into the file whole once it's written
+#[cfg(feature = "file-log")]
+struct SharedLog(Mutex<LogFile>);
+
+#[cfg(feature = "file-log")]
+impl SharedLog {
+    fn new(log: LogFile) -> SharedLog {
+        SharedLog(Mutex::new(log))
+    }
+}
+
+#[cfg(feature = "file-log")]
+impl<'a> MakeWriter<'a> for SharedLog {
+    type Writer = Record<'a>;
+
+    fn make_writer(&'a self) -> Record<'a> {
+        Record {
+            log: &self.0,
+            buf: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "file-log")]
+struct Record<'a> {
+    log: &'a Mutex<LogFile>,
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "file-log")]
+impl Write for Record<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+//nowhere to report a failed write to but the console
+#[cfg(feature = "file-log")]
+impl Drop for Record<'_> {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let mut log = self
+            .log
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(e) = log.append(&self.buf) {
+            eprintln!("Writing to the {} log failed: {}", log.name, e);
+        }
+    }
+}
+
+#[cfg(feature = "file-log")]
+fn log_path(dir: &Path, name: &str, date: &str, index: u32) -> PathBuf {
+    dir.join(format!("{}_{}_{:03}.log", name, date, index))
+}
+
+//files of one log oldest first, the names sort by date then index
+#[cfg(feature = "file-log")]
+fn existing(dir: &Path, name: &str) -> io::Result<Vec<PathBuf>> {
+    let prefix = format!("{}_", name);
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "log")
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(&prefix))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(all(test, feature = "file-log"))]
+mod tests {
+    use super::*;
+
+    //a record that would go over the size starts the next file instead of being split between two
+    #[test]
+    fn rotates_between_records() {
+        let dir = std::env::temp_dir().join(format!("game_thing_logs_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = LogConfig {
+            dir: dir.clone(),
+            max_size_kb: 1,
+            keep: 100,
+        };
+        fs::create_dir_all(&dir).unwrap();
+        let log = SharedLog::new(LogFile::open(&config, "game").unwrap());
+        for i in 0..40 {
+            let mut record = log.make_writer();
+            write!(record, "record {:02} ", i).unwrap();
+            writeln!(record, "{}", "x".repeat(60)).unwrap();
+        }
+
+        let files = existing(&dir, "game").unwrap();
+        assert!(files.len() > 1);
+        let mut lines = Vec::new();
+        for file in files.iter() {
+            let text = fs::read_to_string(file).unwrap();
+            assert!(text.len() <= 1024);
+            lines.extend(text.lines().map(str::to_string));
+        }
+        assert_eq!(lines.len(), 40);
+        assert!(lines.iter().all(|l| l.len() == 70));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
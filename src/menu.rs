@@ -0,0 +1,289 @@
+use bevy::{app::AppExit, prelude::*};
+use log::{error, info};
+
+use crate::{
+    gameplay::{Player, PlayerCount, MAX_PLAYERS},
+    items::{Inventory, ItemRegistry, Items},
+    save::{self, LoadedRun},
+    settings::{Action, Settings},
+    GameState,
+};
+
+pub const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.25);
+pub const HOVER_COLOR: Color = Color::rgb(0.25, 0.25, 0.4);
+
+//--components--//
+
+//top level node of whichever menu is showing
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component, Clone, Copy)]
+pub enum MenuButton {
+    NewRun,
+    //a new run with a second player on a gamepad
+    Coop,
+    Continue,
+    Resume,
+    Options,
+    SaveAndQuit,
+    Quit,
+}
+
+impl MenuButton {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuButton::NewRun => "New Run",
+            MenuButton::Coop => "Co-op",
+            MenuButton::Continue => "Continue",
+            MenuButton::Resume => "Resume",
+            MenuButton::Options => "Options",
+            MenuButton::SaveAndQuit => "Save & Quit",
+            MenuButton::Quit => "Quit",
+        }
+    }
+}
+
+//--resources--//
+
+pub struct UiFont(pub Handle<Font>);
+
+//--systems--//
+
+pub fn spawn_main_menu(mut commands: Commands, font: Res<UiFont>) {
+    let mut buttons = vec![MenuButton::NewRun, MenuButton::Coop];
+    if save::save_exists() {
+        buttons.push(MenuButton::Continue);
+    }
+    buttons.push(MenuButton::Options);
+    buttons.push(MenuButton::Quit);
+
+    spawn_menu(&mut commands, &font.0, "Game Thing", &buttons);
+}
+
+pub fn spawn_pause_menu(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    items: Res<Items>,
+    registries: Res<Assets<ItemRegistry>>,
+    players: Query<(&Player, &Inventory)>,
+) {
+    let root = spawn_menu(
+        &mut commands,
+        &font.0,
+        "Paused",
+        &[
+            MenuButton::Resume,
+            MenuButton::Options,
+            MenuButton::SaveAndQuit,
+        ],
+    );
+
+    //what each player is carrying, under the buttons
+    let style = TextStyle {
+        font: font.0.clone(),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    let mut players: Vec<_> = players.iter().collect();
+    players.sort_by_key(|(p, _)| p.slot);
+    let mut lines = Vec::new();
+    for (player, inventory) in players.iter() {
+        let heading = if players.len() > 1 {
+            format!("Player {}", player.slot + 1)
+        } else {
+            "Inventory".to_string()
+        };
+        lines.push((heading, 28.0));
+        match registries.get(&items.0) {
+            Some(registry) if !inventory.items.is_empty() => lines.extend(
+                inventory
+                    .defs(registry)
+                    .map(|def| (format!("{} - {}", def.name, def.description), 20.0)),
+            ),
+            _ => lines.push(("Nothing yet".to_string(), 20.0)),
+        }
+    }
+    commands.entity(root).with_children(|parent| {
+        parent
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::ColumnReverse,
+                    align_items: AlignItems::Center,
+                    margin: Rect::all(Val::Px(20.0)),
+                    ..Default::default()
+                },
+                color: Color::NONE.into(),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                for (line, size) in lines {
+                    parent.spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(4.0)),
+                            ..Default::default()
+                        },
+                        text: Text::with_section(
+                            line,
+                            TextStyle {
+                                font_size: size,
+                                ..style.clone()
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                }
+            });
+    });
+}
+
+pub fn despawn_menu(mut commands: Commands, q: Query<Entity, With<MenuRoot>>) {
+    for ent in q.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+}
+
+pub fn menu_buttons(
+    mut commands: Commands,
+    mut state: ResMut<State<GameState>>,
+    mut exit: EventWriter<AppExit>,
+    mut q: Query<(&Interaction, &MenuButton, &mut UiColor), Changed<Interaction>>,
+) {
+    for (interaction, button, mut color) in q.iter_mut() {
+        match *interaction {
+            Interaction::Hovered => color.0 = HOVER_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+            Interaction::Clicked => {
+                let res = match button {
+                    MenuButton::NewRun => {
+                        commands.insert_resource(PlayerCount(1));
+                        state.set(GameState::Playing)
+                    }
+                    MenuButton::Coop => {
+                        commands.insert_resource(PlayerCount(MAX_PLAYERS));
+                        state.set(GameState::Playing)
+                    }
+                    MenuButton::Continue => match save::read_snapshot(save::SAVE_PATH) {
+                        Ok(snapshot) => {
+                            info!("Continuing run from {}", save::SAVE_PATH);
+                            commands.insert_resource(PlayerCount(snapshot.players.len()));
+                            commands.insert_resource(LoadedRun(snapshot));
+                            state.set(GameState::Playing)
+                        }
+                        Err(e) => {
+                            error!("Couldn't load save: {:#}", e);
+                            continue;
+                        }
+                    },
+                    MenuButton::Resume => state.pop(),
+                    MenuButton::Options => state.push(GameState::Options),
+                    //the run was already saved when the game was paused
+                    MenuButton::SaveAndQuit => state.replace(GameState::MainMenu),
+                    MenuButton::Quit => {
+                        exit.send(AppExit);
+                        continue;
+                    }
+                };
+                if let Err(e) = res {
+                    error!("Menu state change failed: {:?}", e);
+                }
+            }
+        }
+    }
+}
+
+//the pause key toggles the pause menu
+pub fn pause_input(
+    mut input: ResMut<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let pause = settings.keys.key(Action::Pause);
+    if !input.just_pressed(pause) {
+        return;
+    }
+    //the state change happens this frame, so don't let the new state see the same press
+    input.reset(pause);
+
+    let res = match state.current() {
+        GameState::Playing => state.push(GameState::Paused),
+        GameState::Paused => state.pop(),
+        GameState::Loading | GameState::MainMenu | GameState::Options => Ok(()),
+    };
+    if let Err(e) = res {
+        error!("Pause state change failed: {:?}", e);
+    }
+}
+
+//returns the root so callers can add to it
+fn spawn_menu(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    title: &str,
+    buttons: &[MenuButton],
+) -> Entity {
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 32.0,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(MenuRoot)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(20.0)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    title,
+                    TextStyle {
+                        font_size: 56.0,
+                        ..text_style.clone()
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            for button in buttons {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(260.0), Val::Px(56.0)),
+                            margin: Rect::all(Val::Px(8.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..Default::default()
+                    })
+                    .insert(*button)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                button.label(),
+                                text_style.clone(),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
+        })
+        .id()
+}
@@ -0,0 +1,254 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::GameConfig, gameplay::Player, StepTime};
+
+//seconds between afterimages while dashing, and how long each one takes to fade
+const AFTERIMAGE_GAP: f32 = 0.02;
+const AFTERIMAGE_TIME: f32 = 0.25;
+const AFTERIMAGE_COLOR: Color = Color::rgba(0.6, 0.8, 1.0, 0.5);
+
+//where a velocity modifier came from, adding one from a source that already has one replaces it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModifierSource {
+    Knockback,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Modifier {
+    //added on top of whatever the entity is doing, in pixels per second
+    Push(Vec2),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VelocityModifier {
+    pub source: ModifierSource,
+    pub modifier: Modifier,
+    //seconds left, None lasts until whatever added it takes it off again
+    pub left: Option<f32>,
+}
+
+//--components--//
+
+//everything pushing an entity around that isn't its own movement
+#[derive(Component, Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VelocityModifiers(Vec<VelocityModifier>);
+
+impl VelocityModifiers {
+    pub fn add(&mut self, source: ModifierSource, modifier: Modifier, left: Option<f32>) {
+        self.remove(source);
+        self.0.push(VelocityModifier {
+            source,
+            modifier,
+            left,
+        });
+    }
+
+    pub fn remove(&mut self, source: ModifierSource) {
+        self.0.retain(|m| m.source != source);
+    }
+
+    //the entity's own velocity with every push added on top
+    pub fn apply(&self, own: Vec2) -> Vec2 {
+        self.0.iter().fold(own, |velocity, m| match m.modifier {
+            Modifier::Push(v) => velocity + v,
+        })
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        for m in self.0.iter_mut() {
+            if let Some(ref mut left) = m.left {
+                *left -= delta;
+            }
+        }
+        self.0.retain(|m| m.left.map_or(true, |left| left > 0.0));
+    }
+}
+
+//a burst of speed in the direction the player is moving, on a cooldown
+#[derive(Component, Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Dash {
+    direction: Vec2,
+    //seconds left of the current dash
+    left: f32,
+    //seconds until the next one
+    cooldown: f32,
+    //whether the button was down last step, a dash only goes off as it's pressed
+    held: bool,
+}
+
+impl Dash {
+    pub fn dashing(&self) -> bool {
+        self.left > 0.0
+    }
+
+    //how far it's recharged, 1 when it's ready
+    pub fn charge(&self, config: &GameConfig) -> f32 {
+        1.0 - (self.cooldown / config.dash_cooldown).clamp(0.0, 1.0)
+    }
+
+    //take this step's button, returns whether it's just gone down
+    pub fn press(&mut self, down: bool) -> bool {
+        let was_held = std::mem::replace(&mut self.held, down);
+        down && !was_held
+    }
+
+    //start a dash if one is ready, returns whether it did
+    pub fn start(&mut self, direction: Vec2, config: &GameConfig) -> bool {
+        if self.cooldown > 0.0 || direction == Vec2::ZERO {
+            return false;
+        }
+        self.direction = direction.normalize();
+        self.left = config.dash_time;
+        self.cooldown = config.dash_cooldown;
+        true
+    }
+
+    //the velocity to move at instead of walking, while a dash is going
+    pub fn velocity(&self, config: &GameConfig) -> Option<Vec2> {
+        if self.dashing() {
+            Some(self.direction * config.dash_speed)
+        } else {
+            None
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        self.left = (self.left - delta).max(0.0);
+        self.cooldown = (self.cooldown - delta).max(0.0);
+    }
+}
+
+//spent by sprinting and dashing, comes back once it hasn't been used for a while
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    //seconds since any was last spent
+    rested: f32,
+}
+
+impl Stamina {
+    pub fn new(max: f32) -> Stamina {
+        Stamina {
+            current: max,
+            max,
+            rested: 0.0,
+        }
+    }
+
+    pub fn restore(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+
+    //take some if there's enough for all of it, returns whether it did
+    pub fn spend(&mut self, amount: f32) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.current -= amount;
+        self.rested = 0.0;
+        true
+    }
+
+    //take as much as there is up to amount, for things that use it continuously,
+    //returns whether there was any at all
+    pub fn drain(&mut self, amount: f32) -> bool {
+        if self.current <= 0.0 {
+            return false;
+        }
+        self.current = (self.current - amount).max(0.0);
+        self.rested = 0.0;
+        true
+    }
+
+    //regen_scale multiplies how fast it comes back
+    pub fn recover(&mut self, delta: f32, config: &GameConfig, regen_scale: f32) {
+        self.rested += delta;
+        if self.rested >= config.stamina_delay {
+            self.restore(config.stamina_regen * regen_scale * delta);
+        }
+    }
+}
+
+//nothing can hurt it until this runs out
+#[derive(Component)]
+pub struct Invulnerable(pub f32);
+
+#[derive(Component)]
+pub struct Afterimage(Timer);
+
+//--systems--//
+
+pub fn tick_modifiers(time: Res<StepTime>, mut q: Query<&mut VelocityModifiers>) {
+    for mut modifiers in q.iter_mut() {
+        modifiers.tick(time.delta_seconds());
+    }
+}
+
+pub fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<StepTime>,
+    mut q: Query<(Entity, &mut Invulnerable)>,
+) {
+    for (ent, mut invulnerable) in q.iter_mut() {
+        invulnerable.0 -= time.delta_seconds();
+        if invulnerable.0 <= 0.0 {
+            commands.entity(ent).remove::<Invulnerable>();
+        }
+    }
+}
+
+//leave fading copies of the player behind while it dashes
+pub fn trail_afterimages(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut since: Local<f32>,
+    q: Query<
+        (
+            &Dash,
+            &Transform,
+            &TextureAtlasSprite,
+            &Handle<TextureAtlas>,
+        ),
+        With<Player>,
+    >,
+) {
+    *since += time.delta_seconds();
+    for (dash, transform, sprite, atlas) in q.iter() {
+        if !dash.dashing() || *since < AFTERIMAGE_GAP {
+            continue;
+        }
+        *since = 0.0;
+        let mut transform = *transform;
+        transform.translation.z -= 0.1;
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: atlas.clone(),
+                sprite: TextureAtlasSprite {
+                    index: sprite.index,
+                    color: AFTERIMAGE_COLOR,
+                    ..Default::default()
+                },
+                transform,
+                ..Default::default()
+            })
+            .insert(Afterimage(Timer::from_seconds(AFTERIMAGE_TIME, false)));
+    }
+}
+
+pub fn fade_afterimages(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q: Query<(Entity, &mut Afterimage, &mut TextureAtlasSprite)>,
+) {
+    for (ent, mut image, mut sprite) in q.iter_mut() {
+        if image.0.tick(time.delta()).finished() {
+            commands.entity(ent).despawn();
+            continue;
+        }
+        sprite
+            .color
+            .set_a(AFTERIMAGE_COLOR.a() * image.0.percent_left());
+    }
+}
@@ -0,0 +1,914 @@
+use anyhow::{bail, Context};
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use bevy_rapier2d::prelude::RigidBodyPositionComponent;
+use log::{debug, error, info, warn};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bomb::Bombs,
+    config::GameConfig,
+    enemies::{EnemyRoster, Roster},
+    gameplay::{Enemy, EnemySpawn, Fireball, Player, PlayerInput, PlayerInputs, Reticle},
+    items::Inventory,
+    movement::{Dash, Invulnerable, Stamina, VelocityModifiers},
+    patterns::{ActiveEmitter, PatternAttack, PendingPattern},
+    pickups::{Pickup, PickupTimers},
+    pool::EntityPool,
+    replay::WorldChecksum,
+    save::{RunSnapshot, RunState},
+    timestep::Interpolated,
+    ui::PlayerHP,
+    CurrentAttack, DifficultyTimer, EnemySpr, EnemyTimer, FireballSpr, FireballTimer, GameRng,
+    GameState, RunTime, Score, Wave,
+};
+
+//how many steps ahead local input is scheduled, so the peer's has usually arrived by the time it's needed
+pub const INPUT_DELAY: u32 = 3;
+//anything unacknowledged goes out again this often while the game isn't stepping
+const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+//the peer is given up on after this long without hearing from it
+const TIMEOUT: Duration = Duration::from_secs(5);
+//how long to wait for the other end while setting up a session
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+//the most a single udp datagram can carry
+const MAX_PACKET: usize = 65507;
+//packets are cut into pieces no bigger than this, small enough to get through without the network splitting them
+const FRAGMENT_SIZE: usize = 1200;
+//half-arrived packets this far behind the newest one are given up on
+const MAX_PARTIAL: u32 = 32;
+
+//which end of a session this is, picked on the command line
+pub enum NetMode {
+    Off,
+    Host(u16),
+    Join(String),
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    //the joining end knocking until the host answers
+    Hello,
+    //the host's answer, both ends play with its seed and tunables
+    Welcome {
+        seed: u64,
+        config: GameConfig,
+    },
+    //every local input the peer hasn't acknowledged, the first one is for frame first
+    Inputs {
+        first: u32,
+        inputs: Vec<PlayerInput>,
+        //every frame below this has been received from the peer
+        ack: u32,
+        //the frame the host is going to reconcile at, if it's going to
+        reconcile: Option<u32>,
+    },
+    Checksum {
+        frame: u32,
+        sum: u64,
+    },
+    Reconcile(Box<Reconcile>),
+    ReconcileAck(u32),
+    Bye,
+}
+
+//what actually goes out on the socket, a packet or one piece of one, in order by index
+#[derive(Serialize, Deserialize)]
+struct Fragment {
+    //counts up with every packet sent, so pieces of a resent packet are never mixed with the old ones
+    seq: u32,
+    index: u16,
+    count: u16,
+    bytes: Vec<u8>,
+}
+
+//a packet that fits in one fragment, all the handshake ever needs
+fn unfragmented(datagram: &[u8]) -> Option<Packet> {
+    let fragment: Fragment = bincode::deserialize(datagram).ok()?;
+    if fragment.count != 1 {
+        return None;
+    }
+    bincode::deserialize(&fragment.bytes).ok()
+}
+
+//the host's world at the end of a frame, both ends reset to it when they've drifted apart
+#[derive(Serialize, Deserialize, Clone)]
+struct Reconcile {
+    frame: u32,
+    //the rng both ends carry on with
+    seed: u64,
+    snapshot: RunSnapshot,
+    //each player's physics body by slot, the transform alone doesn't round trip through the physics scale
+    bodies: Vec<Vec2>,
+}
+
+//--resources--//
+
+//a connection to the other player, gameplay only steps once both players' input for the step is in
+pub struct NetSession {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    host: bool,
+    //what the host answers a repeated hello with
+    welcome: Option<(u64, GameConfig)>,
+    //the next frame to be stepped
+    frame: u32,
+    local: BTreeMap<u32, PlayerInput>,
+    remote: BTreeMap<u32, PlayerInput>,
+    //every remote frame below this has arrived, and every local one below peer_ack has got there
+    received: u32,
+    peer_ack: u32,
+    sums: BTreeMap<u32, u64>,
+    remote_sums: BTreeMap<u32, u64>,
+    reconcile_at: Option<u32>,
+    //taken but not applied yet
+    pending: Option<Box<Reconcile>>,
+    //the host keeps sending it until the peer says it has it
+    outgoing: Option<Box<Reconcile>>,
+    //checksums for frames below this were taken before the last reconcile and aren't compared
+    settled: u32,
+    reconciled: Option<u32>,
+    //the next packet's sequence number, and the pieces of packets still arriving by theirs
+    seq: u32,
+    partial: BTreeMap<u32, Vec<Option<Vec<u8>>>>,
+    last_heard: Instant,
+    last_sent: Instant,
+    peer_left: bool,
+    failed: Option<String>,
+    //quit instead of going back to the menu when the session ends
+    pub headless: bool,
+}
+
+impl NetSession {
+    fn new(socket: UdpSocket, peer: SocketAddr, host: bool) -> NetSession {
+        //nobody has input for the first few frames, both ends start them idle
+        let idle: BTreeMap<u32, PlayerInput> = (0..INPUT_DELAY)
+            .map(|f| (f, PlayerInput::default()))
+            .collect();
+        NetSession {
+            socket,
+            peer,
+            host,
+            welcome: None,
+            frame: 0,
+            local: idle.clone(),
+            remote: idle,
+            received: INPUT_DELAY,
+            peer_ack: INPUT_DELAY,
+            sums: BTreeMap::new(),
+            remote_sums: BTreeMap::new(),
+            reconcile_at: None,
+            pending: None,
+            outgoing: None,
+            settled: 0,
+            reconciled: None,
+            seq: 0,
+            partial: BTreeMap::new(),
+            last_heard: Instant::now(),
+            last_sent: Instant::now(),
+            peer_left: false,
+            failed: None,
+            headless: false,
+        }
+    }
+
+    //the host is the first player, whoever joined is the second
+    pub fn slot(&self) -> usize {
+        if self.host {
+            0
+        } else {
+            1
+        }
+    }
+
+    //whether the next frame can be stepped yet
+    pub fn ready(&self) -> bool {
+        self.remote.contains_key(&self.frame) && self.reconcile_at.map_or(true, |r| self.frame <= r)
+    }
+
+    //the frame the last reconcile was taken at
+    pub fn reconciled(&self) -> Option<u32> {
+        self.reconciled
+    }
+
+    fn send(&mut self, packet: &Packet) {
+        let bytes = match bincode::serialize(packet) {
+            Ok(b) => b,
+            Err(e) => {
+                error!("Unable to encode packet: {}", e);
+                return;
+            }
+        };
+        let pieces: Vec<&[u8]> = if bytes.is_empty() {
+            vec![&[]]
+        } else {
+            bytes.chunks(FRAGMENT_SIZE).collect()
+        };
+        let count = match u16::try_from(pieces.len()) {
+            Ok(c) => c,
+            Err(_) => {
+                error!("Packet of {} bytes is too big to send", bytes.len());
+                return;
+            }
+        };
+        let seq = self.seq;
+        self.seq = self.seq.wrapping_add(1);
+        for (index, piece) in (0..).zip(pieces) {
+            let fragment = Fragment {
+                seq,
+                index,
+                count,
+                bytes: piece.to_vec(),
+            };
+            let datagram = match bincode::serialize(&fragment) {
+                Ok(d) => d,
+                Err(e) => {
+                    error!("Unable to encode packet: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = self.socket.send_to(&datagram, self.peer) {
+                debug!("Unable to send to {}: {}", self.peer, e);
+            }
+        }
+        self.last_sent = Instant::now();
+    }
+
+    //put a packet back together from its pieces, returns it once the last one is in
+    fn reassemble(&mut self, datagram: &[u8]) -> Option<Packet> {
+        let fragment: Fragment = match bincode::deserialize(datagram) {
+            Ok(f) => f,
+            Err(e) => {
+                debug!("Dropped a bad fragment from {}: {}", self.peer, e);
+                return None;
+            }
+        };
+        let count = fragment.count as usize;
+        if fragment.index as usize >= count {
+            return None;
+        }
+        let pieces = self
+            .partial
+            .entry(fragment.seq)
+            .or_insert_with(|| vec![None; count]);
+        if pieces.len() != count {
+            return None;
+        }
+        pieces[fragment.index as usize] = Some(fragment.bytes);
+        if pieces.iter().any(Option::is_none) {
+            self.partial = self
+                .partial
+                .split_off(&fragment.seq.saturating_sub(MAX_PARTIAL));
+            return None;
+        }
+
+        let bytes: Vec<u8> = self
+            .partial
+            .remove(&fragment.seq)?
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+        match bincode::deserialize(&bytes) {
+            Ok(packet) => Some(packet),
+            Err(e) => {
+                debug!("Dropped a bad packet from {}: {}", self.peer, e);
+                None
+            }
+        }
+    }
+
+    fn send_inputs(&mut self) {
+        let unacked: Vec<(u32, PlayerInput)> = self
+            .local
+            .range(self.peer_ack..)
+            .map(|(f, i)| (*f, i.clone()))
+            .collect();
+        let first = unacked.first().map_or(self.peer_ack, |(f, _)| *f);
+        let packet = Packet::Inputs {
+            first,
+            inputs: unacked.into_iter().map(|(_, i)| i).collect(),
+            ack: self.received,
+            reconcile: if self.host { self.reconcile_at } else { None },
+        };
+        self.send(&packet);
+    }
+
+    fn receive(&mut self, packet: Packet) {
+        match packet {
+            Packet::Hello => {
+                if let Some((seed, config)) = self.welcome.clone() {
+                    self.send(&Packet::Welcome { seed, config });
+                }
+            }
+            Packet::Welcome { .. } => (),
+            Packet::Inputs {
+                first,
+                inputs,
+                ack,
+                reconcile,
+            } => {
+                for (frame, input) in (first..).zip(inputs) {
+                    if frame >= self.frame {
+                        self.remote.entry(frame).or_insert(input);
+                    }
+                }
+                while self.remote.contains_key(&self.received) {
+                    self.received += 1;
+                }
+                self.peer_ack = self.peer_ack.max(ack);
+                let done = self.peer_ack.min(self.frame);
+                self.local = self.local.split_off(&done);
+
+                if let Some(r) = reconcile {
+                    if !self.host && r >= self.settled && self.reconcile_at.is_none() {
+                        debug!("Host is reconciling at frame {}", r);
+                        self.reconcile_at = Some(r);
+                    }
+                }
+            }
+            Packet::Checksum { frame, sum } => {
+                self.remote_sums.insert(frame, sum);
+                self.compare(frame);
+            }
+            Packet::Reconcile(reconcile) => {
+                let frame = reconcile.frame;
+                if !self.host && frame >= self.settled && self.pending.is_none() {
+                    self.reconcile_at = Some(frame);
+                    self.pending = Some(reconcile);
+                }
+                self.send(&Packet::ReconcileAck(frame));
+            }
+            Packet::ReconcileAck(frame) => {
+                if self.outgoing.as_ref().is_some_and(|r| r.frame == frame) {
+                    self.outgoing = None;
+                }
+            }
+            Packet::Bye => self.peer_left = true,
+        }
+    }
+
+    //check a frame once both ends have a checksum for it, the host reconciles whenever they differ
+    fn compare(&mut self, frame: u32) {
+        let (local, remote) = match (self.sums.get(&frame), self.remote_sums.get(&frame)) {
+            (Some(l), Some(r)) => (*l, *r),
+            _ => return,
+        };
+        self.sums.remove(&frame);
+        self.remote_sums.remove(&frame);
+        if local == remote || frame < self.settled {
+            return;
+        }
+        if !self.host {
+            debug!("Out of step with the host at frame {}", frame);
+            return;
+        }
+        if self.reconcile_at.is_some() || self.outgoing.is_some() {
+            return;
+        }
+        //the peer may have stepped as far as the input already sent to it, so go one past that
+        let at = self.frame + INPUT_DELAY;
+        warn!(
+            "Out of step with the peer at frame {}, reconciling at frame {}",
+            frame, at
+        );
+        self.reconcile_at = Some(at);
+    }
+
+    //old checksums the other end never sent are dropped instead of kept forever
+    fn forget_before(&mut self, frame: u32) {
+        self.sums = self.sums.split_off(&frame);
+        self.remote_sums = self.remote_sums.split_off(&frame);
+    }
+}
+
+//open a session for someone to join, returns the tunables both ends play with
+pub fn host(port: u16, config: &GameConfig) -> anyhow::Result<(NetSession, GameConfig)> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))
+        .with_context(|| format!("Unable to listen on port {}", port))?;
+    accept(socket, config)
+}
+
+//wait for the other player to say hello on an already bound socket
+pub fn accept(socket: UdpSocket, config: &GameConfig) -> anyhow::Result<(NetSession, GameConfig)> {
+    info!("Waiting for a player on {}", socket.local_addr()?);
+    socket.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let mut buf = vec![0; MAX_PACKET];
+    let peer = loop {
+        let (len, from) = socket
+            .recv_from(&mut buf)
+            .context("Nobody joined the session")?;
+        if let Some(Packet::Hello) = unfragmented(&buf[..len]) {
+            break from;
+        }
+    };
+    socket.set_nonblocking(true)?;
+
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let mut config = config.clone();
+    config.seed = Some(seed);
+    let mut session = NetSession::new(socket, peer, true);
+    session.welcome = Some((seed, config.clone()));
+    session.send(&Packet::Welcome {
+        seed,
+        config: config.clone(),
+    });
+    info!("{} joined the session", peer);
+    Ok((session, config))
+}
+
+//join a session someone is hosting, returns the host's tunables
+pub fn join(addr: &str) -> anyhow::Result<(NetSession, GameConfig)> {
+    let peer = addr
+        .to_socket_addrs()
+        .with_context(|| format!("Unable to look up {}", addr))?
+        .next()
+        .with_context(|| format!("{} has no address", addr))?;
+    let local: SocketAddr = if peer.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local).context("Unable to open a socket")?;
+    socket.set_read_timeout(Some(RESEND_INTERVAL * 2))?;
+
+    info!("Joining {}", peer);
+    let started = Instant::now();
+    let mut buf = vec![0; MAX_PACKET];
+    let mut session = NetSession::new(socket, peer, false);
+    let config = loop {
+        if started.elapsed() > CONNECT_TIMEOUT {
+            bail!("{} didn't answer", peer);
+        }
+        session.send(&Packet::Hello);
+        let len = match session.socket.recv_from(&mut buf) {
+            Ok((len, from)) if from == peer => len,
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e).context("Unable to reach the host"),
+        };
+        if let Some(Packet::Welcome { seed, mut config }) = session.reassemble(&buf[..len]) {
+            config.seed = Some(seed);
+            break config;
+        }
+    };
+    //nothing the host sends is trusted any more than the local config file is
+    config
+        .validate()
+        .with_context(|| format!("{} sent an unusable config", peer))?;
+    session.socket.set_nonblocking(true)?;
+    session.last_heard = Instant::now();
+    info!("Joined {}", peer);
+    Ok((session, config))
+}
+
+//the run's timers and totals, set straight from a reconcile
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+pub struct RunProgress<'w, 's> {
+    score: ResMut<'w, Score>,
+    bombs: ResMut<'w, Bombs>,
+    wave: ResMut<'w, Wave>,
+    run_time: ResMut<'w, RunTime>,
+    difficulty: ResMut<'w, DifficultyTimer>,
+    pickup_timers: ResMut<'w, PickupTimers>,
+    spawners: Query<
+        'w,
+        's,
+        (&'static Transform, &'static mut EnemyTimer),
+        (With<EnemySpawn>, Without<Player>),
+    >,
+}
+
+//drives a run with scripted input and reports its checksums, for the tests
+pub struct Probe {
+    frames: u32,
+    //add a point to the score on this frame, so a networked host has something to reconcile
+    perturb_at: Option<u32>,
+    step: u32,
+    report: Arc<Mutex<ProbeReport>>,
+}
+
+#[derive(Default, Clone)]
+pub struct ProbeReport {
+    //the checksum after each frame
+    pub sums: Vec<u64>,
+    pub reconciled: Option<u32>,
+}
+
+impl Probe {
+    #[cfg(test)]
+    pub fn new(frames: u32, perturb_at: Option<u32>, report: Arc<Mutex<ProbeReport>>) -> Probe {
+        Probe {
+            frames,
+            perturb_at,
+            step: 0,
+            report,
+        }
+    }
+}
+
+//--systems--//
+
+//read everything that came in, and give up on the peer once it's gone
+pub fn pump(
+    mut commands: Commands,
+    session: Option<ResMut<NetSession>>,
+    mut state: ResMut<State<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let mut session = match session {
+        Some(s) => s,
+        None => return,
+    };
+
+    let mut buf = vec![0; MAX_PACKET];
+    loop {
+        let (len, from) = match session.socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                debug!("Unable to receive: {}", e);
+                break;
+            }
+        };
+        if from != session.peer {
+            continue;
+        }
+        session.last_heard = Instant::now();
+        if let Some(packet) = session.reassemble(&buf[..len]) {
+            session.receive(packet);
+        }
+    }
+
+    let reason = if let Some(reason) = session.failed.take() {
+        Some(reason)
+    } else if session.last_heard.elapsed() > TIMEOUT {
+        Some("Lost connection to the other player".to_string())
+    } else if session.peer_left && !session.ready() {
+        Some("The other player left".to_string())
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        warn!("{}, ending the session", reason);
+        session.send(&Packet::Bye);
+        commands.remove_resource::<NetSession>();
+        if session.headless {
+            exit.send(AppExit);
+        } else if let Err(e) = state.replace(GameState::MainMenu) {
+            error!("Leaving the session failed: {:?}", e);
+        }
+        return;
+    }
+
+    //keep the peer fed while stalled or paused, in case what was sent last got lost
+    if session.last_sent.elapsed() > RESEND_INTERVAL {
+        session.send_inputs();
+        if let Some(reconcile) = session.outgoing.clone() {
+            session.send(&Packet::Reconcile(reconcile));
+        }
+    }
+}
+
+//trade this step's local input for the peer's, both slots then play out the same on each end
+pub fn exchange_inputs(session: Option<ResMut<NetSession>>, mut inputs: ResMut<PlayerInputs>) {
+    let mut session = match session {
+        Some(s) => s,
+        None => return,
+    };
+    let frame = session.frame;
+    //whatever's local is always read into the first slot, see replay::step_frame
+    session
+        .local
+        .insert(frame + INPUT_DELAY, inputs.get(0).clone());
+
+    let local = session.local.get(&frame).cloned().unwrap_or_default();
+    let remote = session.remote.remove(&frame).unwrap_or_default();
+    inputs.0 = if session.host {
+        vec![local, remote]
+    } else {
+        vec![remote, local]
+    };
+    session.frame += 1;
+    session.send_inputs();
+}
+
+//tell the peer what this step came out as, and have the host take a reconcile when it's due
+#[allow(clippy::type_complexity)]
+pub fn sync_checksums(
+    mut commands: Commands,
+    session: Option<ResMut<NetSession>>,
+    world: WorldChecksum,
+    run: RunState,
+    bodies: Query<(&Player, &RigidBodyPositionComponent)>,
+) {
+    let mut session = match session {
+        Some(s) => s,
+        None => return,
+    };
+    let frame = session.frame - 1;
+    let sum = world.checksum();
+    session.sums.insert(frame, sum);
+    session.send(&Packet::Checksum { frame, sum });
+    session.compare(frame);
+    session.forget_before(frame.saturating_sub(300));
+
+    if !session.host || session.reconcile_at != Some(frame) {
+        return;
+    }
+    let snapshot = match run.snapshot() {
+        Some(s) => s,
+        None => return,
+    };
+    let mut bodies: Vec<_> = bodies.iter().collect();
+    bodies.sort_by_key(|(p, _)| p.slot);
+    let reconcile = Box::new(Reconcile {
+        frame,
+        seed: rand::random(),
+        snapshot,
+        bodies: bodies
+            .into_iter()
+            .map(|(_, body)| {
+                let t = body.position.translation;
+                Vec2::new(t.x, t.y)
+            })
+            .collect(),
+    });
+    session.send(&Packet::Reconcile(reconcile.clone()));
+    commands.insert_resource(GameRng(StdRng::seed_from_u64(reconcile.seed)));
+    session.pending = Some(reconcile.clone());
+    session.outgoing = Some(reconcile);
+}
+
+//reset to the host's world once the frame it was taken at has been stepped, on both ends
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn apply_reconcile(
+    mut commands: Commands,
+    session: Option<ResMut<NetSession>>,
+    asset_server: Res<AssetServer>,
+    mut players: Query<
+        (
+            Entity,
+            &Player,
+            &mut Transform,
+            &mut Interpolated,
+            &mut RigidBodyPositionComponent,
+            &mut PlayerHP,
+            &mut Inventory,
+            &mut CurrentAttack,
+            &mut FireballTimer,
+            (&mut Dash, &mut Stamina, &mut VelocityModifiers),
+        ),
+        Without<Reticle>,
+    >,
+    mut reticles: Query<(&Reticle, &mut Transform), (Without<Player>, Without<EnemySpawn>)>,
+    live: Query<(Entity, Option<&Fireball>), Or<(With<Fireball>, With<Enemy>)>>,
+    leftovers: Query<Entity, Or<(With<ActiveEmitter>, With<PendingPattern>, With<Pickup>)>>,
+    mut projectiles: ResMut<EntityPool<Fireball>>,
+    mut enemies: ResMut<EntityPool<Enemy>>,
+    fire_sp: Res<FireballSpr>,
+    enemy_sp: Res<EnemySpr>,
+    roster: Res<Roster>,
+    rosters: Res<Assets<EnemyRoster>>,
+    mut progress: RunProgress,
+) {
+    let mut session = match session {
+        Some(s) => s,
+        None => return,
+    };
+    match session.pending {
+        Some(ref r) if r.frame + 1 == session.frame => (),
+        _ => return,
+    }
+    let reconcile = match session.pending.take() {
+        Some(r) => r,
+        None => return,
+    };
+    let snapshot = &reconcile.snapshot;
+
+    for (
+        ent,
+        player,
+        mut tr,
+        mut interp,
+        mut body,
+        mut hp,
+        mut inventory,
+        mut attack,
+        mut fire_timer,
+        movement,
+    ) in players.iter_mut()
+    {
+        let (state, at) = match (
+            snapshot.players.get(player.slot),
+            reconcile.bodies.get(player.slot),
+        ) {
+            (Some(s), Some(b)) => (s, *b),
+            _ => continue,
+        };
+        tr.translation = state.position.extend(tr.translation.z);
+        *interp = Interpolated::new(tr.translation);
+        *body = at.into();
+        hp.0 = state.hp;
+        inventory.items = state.items.clone();
+        if attack.0.name() != state.attack {
+            attack.0 = Box::new(PatternAttack::load(&asset_server, &state.attack));
+        }
+        fire_timer.0 = state.fire_timer.to_timer();
+        let (mut dash, mut stamina, mut modifiers) = movement;
+        *dash = state.dash.clone();
+        *stamina = state.stamina.clone();
+        *modifiers = state.modifiers.clone();
+        if state.invulnerable > 0.0 {
+            commands
+                .entity(ent)
+                .insert(Invulnerable(state.invulnerable));
+        } else {
+            commands.entity(ent).remove::<Invulnerable>();
+        }
+        for (_, mut reticle_tr) in reticles.iter_mut().filter(|(r, _)| r.0 == player.slot) {
+            reticle_tr.translation = state.reticle.extend(reticle_tr.translation.z);
+        }
+    }
+
+    //everything else is simpler to put back from scratch
+    for (ent, ball) in live.iter() {
+        if ball.is_some() {
+            projectiles.release(&mut commands, ent);
+        } else {
+            enemies.release(&mut commands, ent);
+        }
+    }
+    for ent in leftovers.iter() {
+        commands.entity(ent).despawn();
+    }
+    for enemy in snapshot.enemies.iter() {
+        enemy.spawn(
+            &mut commands,
+            &mut enemies,
+            &enemy_sp.0,
+            rosters.get(&roster.0),
+        );
+    }
+    for ball in snapshot.projectiles.iter() {
+        ball.spawn(&mut commands, &mut projectiles, &fire_sp.0);
+    }
+    for emitter in snapshot.emitters.iter() {
+        commands.spawn().insert(emitter.clone());
+    }
+    for pattern in snapshot.patterns.iter() {
+        pattern.fire(&mut commands, &asset_server);
+    }
+    for pickup in snapshot.pickups.iter() {
+        pickup.spawn(&mut commands);
+    }
+
+    progress.score.0 = snapshot.score;
+    progress.bombs.0 = snapshot.bombs;
+    progress.wave.0 = snapshot.wave;
+    progress.run_time.0 = snapshot.run_time;
+    progress.difficulty.0 = snapshot.difficulty.to_timer();
+    progress.pickup_timers.bomb = snapshot.bomb_pickup.to_timer();
+    progress.pickup_timers.item = snapshot.item_pickup.to_timer();
+    for (tr, mut timer) in progress.spawners.iter_mut() {
+        let saved = snapshot
+            .spawners
+            .iter()
+            .find(|sp| sp.position.distance(tr.translation.truncate()) < 1.0);
+        if let Some(saved) = saved {
+            timer.0 = saved.timer.to_timer();
+        }
+    }
+    commands.insert_resource(GameRng(StdRng::seed_from_u64(reconcile.seed)));
+
+    session.reconcile_at = None;
+    session.settled = reconcile.frame + 1;
+    session.reconciled = Some(reconcile.frame);
+    info!("Reconciled at frame {}", reconcile.frame);
+}
+
+//say goodbye when leaving the run, the peer would only find out by timing out otherwise
+pub fn leave(mut commands: Commands, session: Option<ResMut<NetSession>>) {
+    if let Some(mut session) = session {
+        session.send(&Packet::Bye);
+        commands.remove_resource::<NetSession>();
+        info!("Left the session");
+    }
+}
+
+pub fn leave_on_exit(
+    mut ev_exit: EventReader<AppExit>,
+    commands: Commands,
+    session: Option<ResMut<NetSession>>,
+) {
+    if ev_exit.iter().next().is_some() {
+        leave(commands, session);
+    }
+}
+
+//play the script in place of the keyboard
+pub fn drive_probe(
+    probe: Option<Res<Probe>>,
+    session: Option<Res<NetSession>>,
+    mut inputs: ResMut<PlayerInputs>,
+    mut score: ResMut<Score>,
+) {
+    let probe = match probe {
+        Some(p) => p,
+        None => return,
+    };
+    let t = probe.step as f32 / 40.0 + session.map_or(0, |s| s.slot()) as f32;
+    inputs.0 = vec![PlayerInput {
+        movement: Vec2::new(t.cos(), t.sin()),
+        sprint: probe.step % 200 < 50,
+        fire: probe.step % 90 > 20,
+        dash: probe.step % 120 == 60,
+        bomb: probe.step == 240,
+        aim: Vec2::new((t * 1.7).cos(), (t * 1.7).sin()),
+    }];
+    if probe.perturb_at == Some(probe.step) {
+        info!("Knocking the score out of step at frame {}", probe.step);
+        score.0 += 1;
+    }
+}
+
+pub fn record_probe(
+    probe: Option<ResMut<Probe>>,
+    session: Option<Res<NetSession>>,
+    world: WorldChecksum,
+    mut exit: EventWriter<AppExit>,
+) {
+    let mut probe = match probe {
+        Some(p) => p,
+        None => return,
+    };
+    let mut report = match probe.report.lock() {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+    report.sums.push(world.checksum());
+    report.reconciled = session.and_then(|s| s.reconciled());
+    drop(report);
+
+    probe.step += 1;
+    if probe.step == probe.frames {
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a packet many times the size of a fragment gets there in one piece
+    #[test]
+    fn fragments_reassemble() {
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        b.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+        let mut sender = NetSession::new(a, b_addr, true);
+        let mut receiver = NetSession::new(b, a_addr, false);
+
+        let inputs = vec![
+            PlayerInput {
+                movement: Vec2::new(0.5, -1.0),
+                fire: true,
+                ..Default::default()
+            };
+            2000
+        ];
+        sender.send(&Packet::Inputs {
+            first: 10,
+            inputs: inputs.clone(),
+            ack: 3,
+            reconcile: None,
+        });
+
+        let mut buf = vec![0; MAX_PACKET];
+        let packet = loop {
+            let (len, _) = receiver.socket.recv_from(&mut buf).unwrap();
+            assert!(len <= FRAGMENT_SIZE + 64);
+            if let Some(p) = receiver.reassemble(&buf[..len]) {
+                break p;
+            }
+        };
+        match packet {
+            Packet::Inputs {
+                first: 10,
+                inputs: received,
+                ..
+            } => assert_eq!(received, inputs),
+            _ => panic!("got the wrong packet back"),
+        }
+        assert!(receiver.partial.is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+use bevy::prelude::*;
+use log::error;
+
+use crate::{
+    menu::{UiFont, BUTTON_COLOR, HOVER_COLOR},
+    settings::{Action, Settings},
+    GameState,
+};
+
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    Master,
+    Music,
+    Sfx,
+}
+
+impl Channel {
+    fn label(&self) -> &'static str {
+        match self {
+            Channel::Master => "Master",
+            Channel::Music => "Music",
+            Channel::Sfx => "SFX",
+        }
+    }
+
+    fn volume(&self, settings: &Settings) -> f32 {
+        match self {
+            Channel::Master => settings.master_volume,
+            Channel::Music => settings.music_volume,
+            Channel::Sfx => settings.sfx_volume,
+        }
+    }
+
+    fn volume_mut<'a>(&self, settings: &'a mut Settings) -> &'a mut f32 {
+        match self {
+            Channel::Master => &mut settings.master_volume,
+            Channel::Music => &mut settings.music_volume,
+            Channel::Sfx => &mut settings.sfx_volume,
+        }
+    }
+}
+
+//--components--//
+
+#[derive(Component)]
+pub struct OptionsRoot;
+
+#[derive(Component, Clone, Copy)]
+pub enum OptionButton {
+    VolumeDown(Channel),
+    VolumeUp(Channel),
+    Display,
+    VSync,
+    Rebind(Action),
+    Back,
+}
+
+//text showing the current value of a setting
+#[derive(Component, Clone, Copy)]
+pub enum OptionValue {
+    Volume(Channel),
+    Display,
+    VSync,
+    Key(Action),
+}
+
+impl OptionValue {
+    fn describe(&self, settings: &Settings, rebinding: &Rebinding) -> String {
+        match self {
+            OptionValue::Volume(channel) => format!(
+                "{} {:>3.0}%",
+                channel.label(),
+                channel.volume(settings) * 100.0
+            ),
+            OptionValue::Display => format!("Window: {}", settings.display.label()),
+            OptionValue::VSync => {
+                format!("VSync: {}", if settings.vsync { "On" } else { "Off" })
+            }
+            OptionValue::Key(action) if rebinding.0 == Some(*action) => {
+                format!("{}: press a key", action.label())
+            }
+            OptionValue::Key(action) => {
+                format!("{}: {:?}", action.label(), settings.keys.key(*action))
+            }
+        }
+    }
+}
+
+//--resources--//
+
+//the action waiting on a key press, if any
+#[derive(Default)]
+pub struct Rebinding(pub Option<Action>);
+
+//--systems--//
+
+pub fn spawn_options(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+) {
+    let style = TextStyle {
+        font: font.0.clone(),
+        font_size: 22.0,
+        color: Color::WHITE,
+    };
+    let describe = |value: OptionValue| value.describe(&settings, &rebinding);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..Default::default()
+        })
+        .insert(OptionsRoot)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(16.0)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Options",
+                    TextStyle {
+                        font_size: 48.0,
+                        ..style.clone()
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexStart,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    //audio and display on the left
+                    parent.spawn_bundle(column()).with_children(|parent| {
+                        for channel in [Channel::Master, Channel::Music, Channel::Sfx] {
+                            parent.spawn_bundle(row()).with_children(|parent| {
+                                spawn_button(
+                                    parent,
+                                    OptionButton::VolumeDown(channel),
+                                    40.0,
+                                    "-".to_string(),
+                                    None,
+                                    &style,
+                                );
+                                let value = OptionValue::Volume(channel);
+                                parent
+                                    .spawn_bundle(TextBundle {
+                                        style: Style {
+                                            margin: Rect::all(Val::Px(8.0)),
+                                            ..Default::default()
+                                        },
+                                        text: Text::with_section(
+                                            describe(value),
+                                            style.clone(),
+                                            Default::default(),
+                                        ),
+                                        ..Default::default()
+                                    })
+                                    .insert(value);
+                                spawn_button(
+                                    parent,
+                                    OptionButton::VolumeUp(channel),
+                                    40.0,
+                                    "+".to_string(),
+                                    None,
+                                    &style,
+                                );
+                            });
+                        }
+                        for (button, value) in [
+                            (OptionButton::Display, OptionValue::Display),
+                            (OptionButton::VSync, OptionValue::VSync),
+                        ] {
+                            spawn_button(
+                                parent,
+                                button,
+                                260.0,
+                                describe(value),
+                                Some(value),
+                                &style,
+                            );
+                        }
+                    });
+
+                    //key bindings on the right
+                    parent.spawn_bundle(column()).with_children(|parent| {
+                        for action in Action::ALL {
+                            let value = OptionValue::Key(action);
+                            spawn_button(
+                                parent,
+                                OptionButton::Rebind(action),
+                                300.0,
+                                describe(value),
+                                Some(value),
+                                &style,
+                            );
+                        }
+                    });
+                });
+
+            spawn_button(
+                parent,
+                OptionButton::Back,
+                200.0,
+                "Back".to_string(),
+                None,
+                &style,
+            );
+        });
+}
+
+pub fn despawn_options(
+    mut commands: Commands,
+    mut rebinding: ResMut<Rebinding>,
+    q: Query<Entity, With<OptionsRoot>>,
+) {
+    for ent in q.iter() {
+        commands.entity(ent).despawn_recursive();
+    }
+    rebinding.0 = None;
+}
+
+pub fn option_buttons(
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut state: ResMut<State<GameState>>,
+    mut q: Query<(&Interaction, &OptionButton, &mut UiColor), Changed<Interaction>>,
+) {
+    for (interaction, button, mut color) in q.iter_mut() {
+        match *interaction {
+            Interaction::Hovered => color.0 = HOVER_COLOR,
+            Interaction::None => color.0 = BUTTON_COLOR,
+            Interaction::Clicked => match *button {
+                OptionButton::VolumeDown(channel) => {
+                    let volume = channel.volume_mut(&mut settings);
+                    *volume = (*volume - VOLUME_STEP).max(0.0);
+                }
+                OptionButton::VolumeUp(channel) => {
+                    let volume = channel.volume_mut(&mut settings);
+                    *volume = (*volume + VOLUME_STEP).min(1.0);
+                }
+                OptionButton::Display => settings.display = settings.display.next(),
+                OptionButton::VSync => settings.vsync = !settings.vsync,
+                //clicking the one already waiting gives up on it
+                OptionButton::Rebind(action) => {
+                    rebinding.0 = if rebinding.0 == Some(action) {
+                        None
+                    } else {
+                        Some(action)
+                    };
+                }
+                OptionButton::Back => {
+                    if let Err(e) = state.pop() {
+                        error!("Options state change failed: {:?}", e);
+                    }
+                }
+            },
+        }
+    }
+}
+
+//bind the next key pressed while waiting on one, otherwise escape goes back
+pub fn options_keys(
+    mut input: ResMut<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if let Some(action) = rebinding.0 {
+        let key = input.get_just_pressed().next().copied();
+        if let Some(key) = key {
+            settings.keys.bind(action, key);
+            rebinding.0 = None;
+            input.reset(key);
+        }
+    } else if input.just_pressed(settings.keys.pause) {
+        let pause = settings.keys.pause;
+        input.reset(pause);
+        if let Err(e) = state.pop() {
+            error!("Options state change failed: {:?}", e);
+        }
+    }
+}
+
+pub fn refresh_options(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    mut q: Query<(&OptionValue, &mut Text)>,
+) {
+    if !settings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (value, mut text) in q.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = value.describe(&settings, &rebinding);
+        }
+    }
+}
+
+fn column() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::ColumnReverse,
+            align_items: AlignItems::Center,
+            margin: Rect::all(Val::Px(12.0)),
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    }
+}
+
+fn row() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    button: OptionButton,
+    width: f32,
+    label: String,
+    value: Option<OptionValue>,
+    style: &TextStyle,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(width), Val::Px(36.0)),
+                margin: Rect::all(Val::Px(4.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: BUTTON_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            let mut text = parent.spawn_bundle(TextBundle {
+                text: Text::with_section(label, style.clone(), Default::default()),
+                ..Default::default()
+            });
+            if let Some(value) = value {
+                text.insert(value);
+            }
+        });
+}
@@ -0,0 +1,406 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use log::debug;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::CosmeticRng;
+
+//drawn over the sprites they come off of
+const PARTICLE_Z: f32 = 5.0;
+
+//every kind of particle burst gameplay can ask for
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Effect {
+    Impact,
+    Death,
+    Muzzle,
+    Shockwave,
+}
+
+//how one effect looks, ranges are (min, max) and picked per particle
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct EffectDef {
+    //particles let out as soon as the effect starts
+    pub burst: u32,
+    //particles per second after that, for as long as duration
+    pub rate: f32,
+    pub duration: f32,
+    //seconds each particle lives
+    pub lifetime: (f32, f32),
+    //pixels per second
+    pub speed: (f32, f32),
+    //degrees either side of the direction the effect was fired in, 180 goes all round
+    pub spread: f32,
+    //speed lost per second, as a fraction
+    pub drag: f32,
+    //colours from birth to death, blended evenly between
+    pub colors: Vec<(f32, f32, f32, f32)>,
+    //sizes in pixels from birth to death, blended the same way
+    pub sizes: Vec<f32>,
+}
+
+impl Default for EffectDef {
+    fn default() -> Self {
+        EffectDef {
+            burst: 8,
+            rate: 0.0,
+            duration: 0.0,
+            lifetime: (0.3, 0.5),
+            speed: (50.0, 150.0),
+            spread: 180.0,
+            drag: 0.0,
+            colors: vec![(1.0, 1.0, 1.0, 1.0), (1.0, 1.0, 1.0, 0.0)],
+            sizes: vec![4.0],
+        }
+    }
+}
+
+impl EffectDef {
+    fn color_at(&self, t: f32) -> Color {
+        let (r, g, b, a) = sample(&self.colors, t, |a, b, t| {
+            (
+                lerp(a.0, b.0, t),
+                lerp(a.1, b.1, t),
+                lerp(a.2, b.2, t),
+                lerp(a.3, b.3, t),
+            )
+        })
+        .unwrap_or((1.0, 1.0, 1.0, 1.0));
+        Color::rgba(r, g, b, a)
+    }
+
+    fn size_at(&self, t: f32) -> f32 {
+        sample(&self.sizes, t, lerp).unwrap_or(4.0)
+    }
+
+    //what to multiply its speeds by so an average particle ends up reach away, drag is left out
+    fn speed_scale(&self, reach: Option<f32>) -> f32 {
+        let travel =
+            (self.speed.0 + self.speed.1) / 2.0 * (self.lifetime.0 + self.lifetime.1) / 2.0;
+        match reach {
+            Some(reach) if travel > 0.0 => reach / travel,
+            _ => 1.0,
+        }
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+//blend between evenly spaced keys, t goes from 0 to 1 over the whole list
+fn sample<T: Copy>(keys: &[T], t: f32, blend: impl Fn(T, T, f32) -> T) -> Option<T> {
+    match keys.len() {
+        0 => None,
+        1 => Some(keys[0]),
+        n => {
+            let pos = t.clamp(0.0, 1.0) * (n - 1) as f32;
+            let i = (pos.floor() as usize).min(n - 2);
+            Some(blend(keys[i], keys[i + 1], pos - i as f32))
+        }
+    }
+}
+
+//all the effects, authored as a .particles.ron file in assets/particles
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "7c1e5a93-2b8d-4f06-a4d1-9e3b6c0f2d58"]
+pub struct ParticleEffects {
+    //live particles are never allowed past this, new ones are dropped instead
+    pub max_particles: usize,
+    pub effects: HashMap<Effect, EffectDef>,
+}
+
+#[derive(Default)]
+pub struct ParticleEffectsLoader;
+
+impl AssetLoader for ParticleEffectsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let effects = ron::de::from_bytes::<ParticleEffects>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(effects));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["particles.ron"]
+    }
+}
+
+//--components--//
+
+//an effect still letting out particles at its rate
+#[derive(Component)]
+pub struct ParticleEmitter {
+    effect: Effect,
+    position: Vec3,
+    direction: Vec2,
+    speed_scale: f32,
+    elapsed: f32,
+    //fraction of a particle owed from earlier frames
+    owed: f32,
+}
+
+#[derive(Component)]
+pub struct Particle {
+    effect: Effect,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+}
+
+//--events--//
+
+//start an effect at a point, direction is where it sprays towards
+pub struct ParticleEvent {
+    pub effect: Effect,
+    pub position: Vec3,
+    pub direction: Vec2,
+    //how far the particles should get, the effect's own speeds are used when unset
+    pub reach: Option<f32>,
+}
+
+impl ParticleEvent {
+    pub fn new(effect: Effect, position: Vec3, direction: Vec2) -> ParticleEvent {
+        ParticleEvent {
+            effect,
+            position,
+            direction,
+            reach: None,
+        }
+    }
+
+    pub fn reaching(self, reach: f32) -> ParticleEvent {
+        ParticleEvent {
+            reach: Some(reach),
+            ..self
+        }
+    }
+}
+
+//--resources--//
+
+//particles are hidden and kept when they die, new ones take one of these before spawning anything
+pub struct ParticlePool {
+    effects: Handle<ParticleEffects>,
+    free: Vec<Entity>,
+    live: usize,
+}
+
+impl ParticlePool {
+    pub fn new(effects: Handle<ParticleEffects>) -> ParticlePool {
+        ParticlePool {
+            effects,
+            free: Vec::new(),
+            live: 0,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn emit(
+        &mut self,
+        commands: &mut Commands,
+        rng: &mut CosmeticRng,
+        def: &EffectDef,
+        cap: usize,
+        effect: Effect,
+        position: Vec3,
+        direction: Vec2,
+        speed_scale: f32,
+    ) {
+        if self.live >= cap {
+            return;
+        }
+        self.live += 1;
+
+        let rng = &mut rng.0;
+        let aim = direction.try_normalize().unwrap_or(Vec2::X);
+        let spread = def.spread.to_radians();
+        let angle = aim.y.atan2(aim.x) + rng.gen_range(-spread..=spread);
+        let speed = rng.gen_range(def.speed.0..=def.speed.1.max(def.speed.0));
+        let lifetime = rng.gen_range(def.lifetime.0..=def.lifetime.1.max(def.lifetime.0));
+
+        let particle = Particle {
+            effect,
+            velocity: Vec2::new(angle.cos(), angle.sin()) * speed * speed_scale,
+            age: 0.0,
+            lifetime,
+        };
+        let sprite = Sprite {
+            color: def.color_at(0.0),
+            custom_size: Some(Vec2::splat(def.size_at(0.0))),
+            ..Default::default()
+        };
+        let transform = Transform::from_translation(position.truncate().extend(PARTICLE_Z));
+
+        match self.free.pop() {
+            Some(ent) => {
+                commands
+                    .entity(ent)
+                    .insert(particle)
+                    .insert(sprite)
+                    .insert(transform)
+                    .insert(Visibility { is_visible: true });
+            }
+            None => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite,
+                        transform,
+                        ..Default::default()
+                    })
+                    .insert(particle);
+            }
+        }
+    }
+}
+
+//--systems--//
+
+//start every effect asked for this frame
+pub fn start_effects(
+    mut commands: Commands,
+    mut events: EventReader<ParticleEvent>,
+    mut pool: ResMut<ParticlePool>,
+    mut rng: ResMut<CosmeticRng>,
+    assets: Res<Assets<ParticleEffects>>,
+) {
+    let effects = match assets.get(&pool.effects) {
+        Some(e) => e,
+        None => return,
+    };
+    for ev in events.iter() {
+        let def = match effects.effects.get(&ev.effect) {
+            Some(d) => d,
+            None => continue,
+        };
+        let speed_scale = def.speed_scale(ev.reach);
+        for _ in 0..def.burst {
+            pool.emit(
+                &mut commands,
+                &mut rng,
+                def,
+                effects.max_particles,
+                ev.effect,
+                ev.position,
+                ev.direction,
+                speed_scale,
+            );
+        }
+        if def.rate > 0.0 && def.duration > 0.0 {
+            commands.spawn().insert(ParticleEmitter {
+                effect: ev.effect,
+                position: ev.position,
+                direction: ev.direction,
+                speed_scale,
+                elapsed: 0.0,
+                owed: 0.0,
+            });
+        }
+    }
+}
+
+pub fn run_emitters(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut rng: ResMut<CosmeticRng>,
+    assets: Res<Assets<ParticleEffects>>,
+    mut q: Query<(Entity, &mut ParticleEmitter)>,
+) {
+    let effects = match assets.get(&pool.effects) {
+        Some(e) => e,
+        None => return,
+    };
+    for (ent, mut emitter) in q.iter_mut() {
+        let def = match effects.effects.get(&emitter.effect) {
+            Some(d) => d,
+            None => {
+                commands.entity(ent).despawn();
+                continue;
+            }
+        };
+        let delta = time.delta_seconds().min(def.duration - emitter.elapsed);
+        emitter.elapsed += delta;
+        emitter.owed += def.rate * delta;
+        while emitter.owed >= 1.0 {
+            emitter.owed -= 1.0;
+            pool.emit(
+                &mut commands,
+                &mut rng,
+                def,
+                effects.max_particles,
+                emitter.effect,
+                emitter.position,
+                emitter.direction,
+                emitter.speed_scale,
+            );
+        }
+        if emitter.elapsed >= def.duration {
+            commands.entity(ent).despawn();
+        }
+    }
+}
+
+//move, fade and shrink live particles, putting dead ones back in the pool
+pub fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    assets: Res<Assets<ParticleEffects>>,
+    mut q: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let effects = assets.get(&pool.effects);
+    let delta = time.delta_seconds();
+    for (ent, mut particle, mut transform, mut sprite) in q.iter_mut() {
+        particle.age += delta;
+        let def = match effects.and_then(|e| e.effects.get(&particle.effect)) {
+            Some(d) if particle.age < particle.lifetime => d,
+            _ => {
+                commands
+                    .entity(ent)
+                    .remove::<Particle>()
+                    .insert(Visibility { is_visible: false });
+                pool.free.push(ent);
+                pool.live = pool.live.saturating_sub(1);
+                continue;
+            }
+        };
+
+        let drag = (1.0 - def.drag * delta).max(0.0);
+        particle.velocity *= drag;
+        transform.translation += (particle.velocity * delta).extend(0.0);
+
+        let t = particle.age / particle.lifetime;
+        sprite.color = def.color_at(t);
+        sprite.custom_size = Some(Vec2::splat(def.size_at(t)));
+    }
+}
+
+//nothing from a run is left flying around the menu
+pub fn clear_particles(
+    mut commands: Commands,
+    mut pool: ResMut<ParticlePool>,
+    particles: Query<Entity, With<Particle>>,
+    emitters: Query<Entity, With<ParticleEmitter>>,
+) {
+    for ent in particles
+        .iter()
+        .chain(emitters.iter())
+        .chain(pool.free.drain(..))
+    {
+        commands.entity(ent).despawn();
+    }
+    pool.live = 0;
+    debug!("Cleared particles");
+}
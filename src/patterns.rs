@@ -0,0 +1,436 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    animation,
+    attacks::Attack,
+    hits::{Hostile, Pierce},
+    pool::EntityPool,
+    status::{Inflicts, StatusDef},
+    timestep::{Interpolated, FIXED_STEP},
+    Collider, Fireball, FireballSpr, Hitbox,
+};
+
+//a firing pattern authored as a .pattern.ron file in assets/patterns
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "5d3c8f0e-7a41-4c2b-9a57-1f0d6b2e8c93"]
+pub struct BulletPattern {
+    //shown next to the attack's name on the HUD, the fireball stands in without one
+    #[serde(default)]
+    pub icon: Option<String>,
+    pub emitters: Vec<Emitter>,
+}
+
+//one ring/fan of projectiles, angles are in degrees and times in seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Emitter {
+    //projectiles per volley
+    pub count: u32,
+    //total arc the projectiles of a volley are spread over
+    pub spread: f32,
+    //how fast the whole fan turns between volleys, in degrees per second
+    pub rotation_speed: f32,
+    //wait before the first volley
+    pub delay: f32,
+    pub volleys: u32,
+    pub interval: f32,
+    pub speed: f32,
+    //health taken off whatever each projectile hits
+    pub damage: f32,
+    //put on whatever each projectile hits
+    pub statuses: Vec<StatusDef>,
+    //fired by enemies, so it hits the player instead of them
+    pub hostile: bool,
+    //enemies each projectile goes through before it's used up
+    pub pierce: u32,
+    //fired from wherever a projectile of this emitter hits something
+    pub on_death: Vec<Emitter>,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Emitter {
+            count: 1,
+            spread: 0.0,
+            rotation_speed: 0.0,
+            delay: 0.0,
+            volleys: 1,
+            interval: 0.0,
+            speed: 500.0,
+            damage: 1.0,
+            statuses: Vec::new(),
+            hostile: false,
+            pierce: 0,
+            on_death: Vec::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PatternLoader;
+
+impl AssetLoader for PatternLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let pattern = ron::de::from_bytes::<BulletPattern>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(pattern));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pattern.ron"]
+    }
+}
+
+//--components--//
+
+//a pattern that has been fired but not yet expanded into emitters
+#[derive(Component)]
+pub struct PendingPattern {
+    pub pattern: Handle<BulletPattern>,
+    pub origin: Vec3,
+    pub target: Vec3,
+    pub owner: usize,
+}
+
+//a running emitter, despawned once all of its volleys are out
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ActiveEmitter {
+    emitter: Emitter,
+    origin: Vec3,
+    direction: Vec2,
+    //fixed steps it has run for, counted rather than summing seconds so every peer turns it the same
+    steps: u32,
+    fired: u32,
+    //passed on to every projectile it fires
+    owner: usize,
+    //the pattern file and which of its emitters this is, so edits to the file reach volleys still to come
+    source: Option<(String, usize)>,
+}
+
+impl ActiveEmitter {
+    pub fn new(emitter: Emitter, origin: Vec3, direction: Vec2, owner: usize) -> ActiveEmitter {
+        ActiveEmitter {
+            emitter,
+            origin,
+            direction,
+            steps: 0,
+            fired: 0,
+            owner,
+            source: None,
+        }
+    }
+
+    pub fn out_of(mut self, path: String, index: usize) -> ActiveEmitter {
+        self.source = Some((path, index));
+        self
+    }
+
+    fn elapsed(&self) -> f32 {
+        self.steps as f32 * FIXED_STEP
+    }
+
+    //how far the fan has turned from the aim, in radians
+    fn turn(&self) -> f32 {
+        (self.emitter.rotation_speed * self.elapsed()).to_radians()
+    }
+}
+
+//sub-emitters carried by a projectile
+#[derive(Component)]
+pub struct OnDeath(pub Vec<Emitter>);
+
+//--attacks--//
+
+//an attack that fires whatever pattern asset it points at, so edits to the file apply on the next shot
+pub struct PatternAttack {
+    path: String,
+    pattern: Handle<BulletPattern>,
+}
+
+impl PatternAttack {
+    pub fn load(asset_server: &AssetServer, path: &str) -> PatternAttack {
+        PatternAttack {
+            path: path.to_string(),
+            pattern: asset_server.load(path),
+        }
+    }
+}
+
+impl Attack for PatternAttack {
+    fn attack(
+        &self,
+        commands: &mut Commands,
+        origin: &Vec3,
+        target: &Vec3,
+        _: &Handle<TextureAtlas>,
+        owner: usize,
+    ) {
+        fire_pattern(commands, &self.pattern, *origin, *target, owner);
+    }
+
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    fn icon<'a>(&self, patterns: &'a Assets<BulletPattern>) -> Option<&'a str> {
+        patterns.get(&self.pattern)?.icon.as_deref()
+    }
+}
+
+//fire a pattern from origin towards target, it's expanded into emitters once the asset is loaded
+pub fn fire_pattern(
+    commands: &mut Commands,
+    pattern: &Handle<BulletPattern>,
+    origin: Vec3,
+    target: Vec3,
+    owner: usize,
+) {
+    commands.spawn().insert(PendingPattern {
+        pattern: pattern.clone(),
+        origin,
+        target,
+        owner,
+    });
+}
+
+//--systems--//
+
+//turn fired patterns into emitters once the asset is available
+pub fn expand_patterns(
+    mut commands: Commands,
+    patterns: Res<Assets<BulletPattern>>,
+    asset_server: Res<AssetServer>,
+    q: Query<(Entity, &PendingPattern)>,
+) {
+    for (ent, pending) in q.iter() {
+        let pattern = match patterns.get(&pending.pattern) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let direction = (pending.target - pending.origin).truncate();
+        let path = asset_server
+            .get_handle_path(&pending.pattern)
+            .map(|p| p.path().to_string_lossy().into_owned());
+        for (index, emitter) in pattern.emitters.iter().enumerate() {
+            let mut active =
+                ActiveEmitter::new(emitter.clone(), pending.origin, direction, pending.owner);
+            if let Some(ref path) = path {
+                active = active.out_of(path.clone(), index);
+            }
+            commands.spawn().insert(active);
+        }
+        commands.entity(ent).despawn();
+    }
+}
+
+//put edits to a pattern file into the emitters it already started
+pub fn refresh_emitters(
+    mut events: EventReader<AssetEvent<BulletPattern>>,
+    patterns: Res<Assets<BulletPattern>>,
+    asset_server: Res<AssetServer>,
+    mut q: Query<&mut ActiveEmitter>,
+) {
+    for ev in events.iter() {
+        let handle = match ev {
+            AssetEvent::Modified { handle } => handle,
+            _ => continue,
+        };
+        let (path, pattern) = match (asset_server.get_handle_path(handle), patterns.get(handle)) {
+            (Some(path), Some(pattern)) => (path, pattern),
+            _ => continue,
+        };
+        let path = path.path().to_string_lossy();
+        for mut active in q.iter_mut() {
+            let emitter = match active.source {
+                Some((ref from, index)) if *from == path => pattern.emitters.get(index),
+                _ => continue,
+            };
+            //an emitter taken out of the file just finishes what it was doing
+            match emitter {
+                Some(emitter) if *emitter != active.emitter => active.emitter = emitter.clone(),
+                _ => (),
+            }
+        }
+    }
+}
+
+//fire the volleys of every active emitter as their delay and interval come up
+pub fn run_emitters(
+    mut commands: Commands,
+    fire_sp: Res<FireballSpr>,
+    mut pool: ResMut<EntityPool<Fireball>>,
+    mut q: Query<(Entity, &mut ActiveEmitter)>,
+) {
+    for (ent, mut active) in q.iter_mut() {
+        active.steps += 1;
+
+        while active.fired < active.emitter.volleys
+            && active.elapsed()
+                >= active.emitter.delay + active.emitter.interval * active.fired as f32
+        {
+            for dir in volley_directions(&active.emitter, active.direction, active.turn()) {
+                let ball = Fireball {
+                    origin: active.origin,
+                    target: active.origin + dir.extend(0.0) * 100.0,
+                    speed: active.emitter.speed,
+                    damage: active.emitter.damage,
+                    owner: active.owner,
+                };
+                let ball = spawn_projectile(
+                    &mut commands,
+                    &mut pool,
+                    &fire_sp.0,
+                    active.origin,
+                    ball,
+                    &active.emitter.on_death,
+                    active.emitter.pierce,
+                    &active.emitter.statuses,
+                );
+                if active.emitter.hostile {
+                    commands.entity(ball).insert(Hostile);
+                }
+            }
+            active.fired += 1;
+        }
+
+        if active.fired >= active.emitter.volleys {
+            commands.entity(ent).despawn();
+        }
+    }
+}
+
+//directions of each projectile in a single volley, fanned out evenly around the aim
+fn volley_directions(emitter: &Emitter, aim: Vec2, turn: f32) -> Vec<Vec2> {
+    let aim = aim.try_normalize().unwrap_or(Vec2::X);
+    let base = aim.y.atan2(aim.x) + turn;
+    let spread = emitter.spread.to_radians();
+
+    (0..emitter.count)
+        .map(|i| {
+            let offset = if emitter.count > 1 {
+                -spread / 2.0 + spread * i as f32 / (emitter.count - 1) as f32
+            } else {
+                0.0
+            };
+            Vec2::new((base + offset).cos(), (base + offset).sin())
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    pool: &mut EntityPool<Fireball>,
+    fire_sp: &Handle<TextureAtlas>,
+    position: Vec3,
+    fireball: Fireball,
+    on_death: &[Emitter],
+    pierce: u32,
+    statuses: &[StatusDef],
+) -> Entity {
+    let mut ball = pool.acquire(commands);
+    ball.insert_bundle(SpriteSheetBundle {
+        texture_atlas: fire_sp.clone(),
+        transform: Transform::from_translation(position),
+        ..Default::default()
+    });
+    ball.insert(fireball)
+        .insert(animation::fireball())
+        .insert(Interpolated::new(position))
+        .insert(Collider::Projectile)
+        .insert(Hitbox(crate::attacks::fireball_size()));
+
+    if !on_death.is_empty() {
+        ball.insert(OnDeath(on_death.to_vec()));
+    }
+    if pierce > 0 {
+        ball.insert(Pierce::new(pierce));
+    }
+    if !statuses.is_empty() {
+        ball.insert(Inflicts(statuses.to_vec()));
+    }
+    ball.id()
+}
+
+//start the sub-emitters of a projectile that just hit something
+pub fn fire_on_death(commands: &mut Commands, on_death: &OnDeath, fireball: &Fireball, at: Vec3) {
+    let direction = (fireball.target - fireball.origin).truncate();
+    for emitter in on_death.0.iter() {
+        commands.spawn().insert(ActiveEmitter::new(
+            emitter.clone(),
+            at,
+            direction,
+            fireball.owner,
+        ));
+    }
+    debug!("Fired {} sub-emitters", on_death.0.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fan(count: u32, spread: f32) -> Emitter {
+        Emitter {
+            count,
+            spread,
+            ..Default::default()
+        }
+    }
+
+    fn assert_near(got: &[Vec2], expected: &[Vec2]) {
+        assert_eq!(got.len(), expected.len());
+        for (got, expected) in got.iter().zip(expected) {
+            assert!(
+                got.distance(*expected) < 1e-5,
+                "{:?} isn't {:?}",
+                got,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn one_projectile_goes_straight_at_the_aim() {
+        let dirs = volley_directions(&fan(1, 90.0), Vec2::new(0.0, 5.0), 0.0);
+        assert_near(&dirs, &[Vec2::Y]);
+    }
+
+    #[test]
+    fn spread_fans_out_evenly_either_side() {
+        let dirs = volley_directions(&fan(3, 180.0), Vec2::X, 0.0);
+        assert_near(&dirs, &[-Vec2::Y, Vec2::X, Vec2::Y]);
+    }
+
+    #[test]
+    fn no_aim_fires_along_x_and_turn_rotates_everything() {
+        let dirs = volley_directions(&fan(2, 180.0), Vec2::ZERO, std::f32::consts::FRAC_PI_2);
+        assert_near(&dirs, &[Vec2::X, -Vec2::X]);
+    }
+
+    #[test]
+    fn turn_follows_the_steps_run() {
+        let emitter = Emitter {
+            rotation_speed: 90.0,
+            ..Default::default()
+        };
+        let mut active = ActiveEmitter::new(emitter, Vec3::ZERO, Vec2::X, 0);
+        assert_eq!(active.turn(), 0.0);
+        //a second of steps
+        active.steps = (1.0 / FIXED_STEP).round() as u32;
+        assert!((active.turn() - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+    }
+}
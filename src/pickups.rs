@@ -0,0 +1,131 @@
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use log::info;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bomb::Bombs,
+    config::GameConfig,
+    gameplay::Player,
+    items::{Inventory, ItemRegistry, Items},
+    sfx::SoundEvent,
+    GameRng, Hitbox, StepTime,
+};
+
+//kept this far in from the edges of the screen
+const MARGIN: f32 = 120.0;
+const PICKUP_SIZE: f32 = 14.0;
+
+//--components--//
+
+//something lying around for the player to walk over
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Pickup {
+    Bomb,
+    //a passive item by its id in the registry
+    Item(String),
+}
+
+impl Pickup {
+    fn color(&self) -> Color {
+        match self {
+            Pickup::Bomb => Color::rgb(1.0, 0.45, 0.2),
+            Pickup::Item(_) => Color::rgb(0.95, 0.85, 0.3),
+        }
+    }
+}
+
+//--resources--//
+
+//count down to the next of each pickup being dropped, start over every run
+pub struct PickupTimers {
+    pub bomb: Timer,
+    pub item: Timer,
+}
+
+//--systems--//
+
+//drop a bomb or an item somewhere on the field every so often
+pub fn spawn_pickups(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    time: Res<StepTime>,
+    mut timers: ResMut<PickupTimers>,
+    mut rng: ResMut<GameRng>,
+    items: Res<Items>,
+    registries: Res<Assets<ItemRegistry>>,
+) {
+    let mut drops = Vec::new();
+    if timers.bomb.tick(time.delta()).just_finished() {
+        drops.push(Pickup::Bomb);
+    }
+    if timers.item.tick(time.delta()).just_finished() {
+        match registries.get(&items.0) {
+            Some(registry) if !registry.items.is_empty() => {
+                let i = rng.0.gen_range(0..registry.items.len());
+                drops.extend(registry.items.keys().nth(i).cloned().map(Pickup::Item));
+            }
+            _ => (),
+        }
+    }
+
+    let half_size = (config.half_size() - Vec2::splat(MARGIN)).max(Vec2::ZERO);
+    for pickup in drops {
+        let position = Vec2::new(
+            rng.0.gen_range(-half_size.x..=half_size.x),
+            rng.0.gen_range(-half_size.y..=half_size.y),
+        );
+        spawn_pickup(&mut commands, pickup, position);
+    }
+}
+
+pub fn spawn_pickup(commands: &mut Commands, pickup: Pickup, position: Vec2) {
+    info!("Dropped a {:?} pickup at {}", pickup, position);
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(PICKUP_SIZE)),
+                color: pickup.color(),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(position.extend(0.0)),
+            ..Default::default()
+        })
+        .insert(pickup)
+        .insert(Hitbox(Vec2::splat(PICKUP_SIZE)));
+}
+
+//whatever the player walks over is theirs, unless they can't carry any more of it
+pub fn collect_pickups(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    mut bombs: ResMut<Bombs>,
+    mut player: Query<(&Transform, &Hitbox, &mut Inventory), With<Player>>,
+    pickups: Query<(Entity, &Transform, &Hitbox, &Pickup)>,
+    mut sounds: EventWriter<SoundEvent>,
+) {
+    for (player_tr, player_box, mut inventory) in player.iter_mut() {
+        for (ent, tr, hitbox, pickup) in pickups.iter() {
+            if collide(
+                player_tr.translation,
+                player_box.0,
+                tr.translation,
+                hitbox.0,
+            )
+            .is_none()
+            {
+                continue;
+            }
+            match pickup {
+                Pickup::Bomb if bombs.0 < config.max_bombs => bombs.0 += 1,
+                Pickup::Bomb => continue,
+                Pickup::Item(id) => {
+                    info!("Picked up {}", id);
+                    inventory.items.push(id.clone());
+                }
+            }
+            commands.entity(ent).despawn();
+            sounds.send(SoundEvent::Pickup);
+        }
+    }
+}
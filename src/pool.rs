@@ -0,0 +1,167 @@
+use bevy::{app::AppExit, ecs::system::EntityCommands, prelude::*};
+use log::info;
+use std::{collections::HashSet, marker::PhantomData};
+
+use crate::{
+    animation::Animation,
+    damage::HitFlash,
+    gameplay::Enemy,
+    hits::{Despawning, Hostile, Pierce},
+    patterns::OnDeath,
+    status::{Inflicts, Statuses},
+    timestep::Interpolated,
+    Collider, Fireball, Hitbox,
+};
+
+//things that can go back in a pool, they take off whatever makes them part of the game
+pub trait Poolable: Component {
+    const NAME: &'static str;
+
+    fn strip(entity: &mut EntityCommands);
+}
+
+impl Poolable for Fireball {
+    const NAME: &'static str = "Projectile";
+
+    fn strip(entity: &mut EntityCommands) {
+        entity
+            .remove::<Fireball>()
+            .remove::<OnDeath>()
+            .remove::<Pierce>()
+            .remove::<Inflicts>()
+            .remove::<Hostile>()
+            .remove::<Despawning>()
+            .remove::<Collider>()
+            .remove::<Hitbox>()
+            .remove::<Interpolated>()
+            .remove::<Animation>();
+    }
+}
+
+impl Poolable for Enemy {
+    const NAME: &'static str = "Enemy";
+
+    fn strip(entity: &mut EntityCommands) {
+        entity
+            .remove::<Enemy>()
+            .remove::<Despawning>()
+            .remove::<HitFlash>()
+            .remove::<Statuses>()
+            .remove::<Collider>()
+            .remove::<Hitbox>()
+            .remove::<Interpolated>()
+            .remove::<Animation>();
+    }
+}
+
+//--components--//
+
+//belongs to a pool, kept around hidden between uses
+#[derive(Component)]
+pub struct Pooled;
+
+//--resources--//
+
+//hidden entities waiting to be reused as a T instead of spawning new ones
+pub struct EntityPool<T: Poolable> {
+    free: Vec<Entity>,
+    //released this step, their removals haven't been applied yet so they can't be handed out
+    pending: Vec<Entity>,
+    //everything in free or pending, so a second release is caught without searching both
+    held: HashSet<Entity>,
+    pub hits: u64,
+    pub misses: u64,
+    pub released: u64,
+    marker: PhantomData<T>,
+}
+
+impl<T: Poolable> Default for EntityPool<T> {
+    fn default() -> Self {
+        EntityPool {
+            free: Vec::new(),
+            pending: Vec::new(),
+            held: HashSet::new(),
+            hits: 0,
+            misses: 0,
+            released: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Poolable> EntityPool<T> {
+    //an entity to build a T on, insert a whole sprite bundle since a reused one still has the old
+    pub fn acquire<'w, 's, 'a>(
+        &mut self,
+        commands: &'a mut Commands<'w, 's>,
+    ) -> EntityCommands<'w, 's, 'a> {
+        match self.free.pop() {
+            Some(ent) => {
+                self.held.remove(&ent);
+                self.hits += 1;
+                commands.entity(ent)
+            }
+            None => {
+                self.misses += 1;
+                let mut entity = commands.spawn();
+                entity.insert(Pooled);
+                entity
+            }
+        }
+    }
+
+    //hide it and keep it instead of despawning, releasing the same one twice does nothing
+    pub fn release(&mut self, commands: &mut Commands, ent: Entity) {
+        if !self.held.insert(ent) {
+            return;
+        }
+        let mut entity = commands.entity(ent);
+        T::strip(&mut entity);
+        entity.insert(Visibility { is_visible: false });
+        self.pending.push(ent);
+        self.released += 1;
+    }
+
+    //fraction of acquires that reused an entity
+    pub fn hit_rate(&self) -> f32 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f32 / total as f32
+        }
+    }
+
+    pub fn report(&self) {
+        if self.hits + self.misses == 0 {
+            return;
+        }
+        info!(
+            "{} pool: {} reused, {} spawned, {} released, {:.0}% hit rate",
+            T::NAME,
+            self.hits,
+            self.misses,
+            self.released,
+            self.hit_rate() * 100.0
+        );
+    }
+
+    //forget every entity, for when they've all been despawned
+    pub fn clear(&mut self) {
+        *self = EntityPool::default();
+    }
+}
+
+//--systems--//
+
+//what was released last step has been stripped by now, so it can go out again
+pub fn recycle<T: Poolable>(mut pool: ResMut<EntityPool<T>>) {
+    let pool = &mut *pool;
+    pool.free.append(&mut pool.pending);
+}
+
+pub fn report_on_exit<T: Poolable>(mut exit: EventReader<AppExit>, pool: Res<EntityPool<T>>) {
+    if exit.iter().next().is_some() {
+        pool.report();
+    }
+}
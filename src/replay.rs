@@ -0,0 +1,323 @@
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use bevy_rapier2d::prelude::IntegrationParameters;
+use log::{error, info};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    config::GameConfig,
+    gameplay::{PlayerCount, PlayerInput, PlayerInputs},
+    save::{LoadedRun, RunSnapshot, RunState},
+    settings::Settings,
+    timestep::FIXED_STEP,
+    ExitStatus, GameRng, StepTime,
+};
+
+//bump this whenever the layout of Replay changes
+pub const REPLAY_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayFrame {
+    pub delta: f32,
+    //one per player, by slot
+    pub inputs: Vec<PlayerInput>,
+    //hash of the world after this frame's gameplay, compared against during playback
+    pub checksum: u64,
+}
+
+//one run's worth of inputs, enough to play it back exactly
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub players: usize,
+    //the tunables the run was played with, playback uses these instead of the local config
+    pub config: GameConfig,
+    //the save the run was continued from, if it didn't start fresh
+    pub start: Option<RunSnapshot>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        players: usize,
+        config: GameConfig,
+        start: Option<RunSnapshot>,
+    ) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            players,
+            config,
+            start,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Replay> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Unable to read {}: {}", path.display(), e))?;
+        let replay: Replay = ron::from_str(&text)?;
+        if replay.version != REPLAY_VERSION {
+            anyhow::bail!(
+                "{} is replay version {}, this build reads version {}",
+                path.display(),
+                replay.version,
+                REPLAY_VERSION
+            );
+        }
+        Ok(replay)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+//what this session does with replays, picked on the command line
+pub enum ReplayMode {
+    Off,
+    Record(PathBuf),
+    Playback(PathBuf),
+}
+
+//--resources--//
+
+pub struct Recorder {
+    path: PathBuf,
+    replay: Option<Replay>,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf) -> Recorder {
+        Recorder { path, replay: None }
+    }
+}
+
+pub struct Playback {
+    replay: Replay,
+    frame: usize,
+    diverged: Option<usize>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay,
+            frame: 0,
+            diverged: None,
+        }
+    }
+
+    pub fn start(&self) -> Option<LoadedRun> {
+        self.replay.start.clone().map(LoadedRun)
+    }
+
+    pub fn config(&self) -> GameConfig {
+        self.replay.config.clone()
+    }
+
+    pub fn players(&self) -> PlayerCount {
+        PlayerCount(self.replay.players)
+    }
+}
+
+//the checksum is taken over everything a snapshot holds, so a reconcile puts back everything it compares
+#[derive(SystemParam)]
+pub struct WorldChecksum<'w, 's> {
+    run: RunState<'w, 's>,
+}
+
+impl<'w, 's> WorldChecksum<'w, 's> {
+    //order independent, entities come out of queries in whatever order they were spawned in
+    pub fn checksum(&self) -> u64 {
+        let mut snapshot = match self.run.snapshot() {
+            Some(s) => s,
+            None => return 0,
+        };
+        let lists = [
+            unordered(&snapshot.spawners),
+            unordered(&snapshot.enemies),
+            unordered(&snapshot.projectiles),
+            unordered(&snapshot.emitters),
+            unordered(&snapshot.patterns),
+            unordered(&snapshot.pickups),
+        ];
+        snapshot.spawners.clear();
+        snapshot.enemies.clear();
+        snapshot.projectiles.clear();
+        snapshot.emitters.clear();
+        snapshot.patterns.clear();
+        snapshot.pickups.clear();
+        hash_of(&(snapshot, lists))
+    }
+}
+
+fn hash_of<T: Serialize>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bincode::serialize(value)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+fn unordered<T: Serialize>(items: &[T]) -> u64 {
+    items.iter().map(hash_of).fold(0, u64::wrapping_add)
+}
+
+//--systems--//
+
+//seed the run's rng and start a fresh recording
+pub fn begin_run(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    players: Res<PlayerCount>,
+    loaded: Option<Res<LoadedRun>>,
+    recorder: Option<ResMut<Recorder>>,
+    playback: Option<Res<Playback>>,
+) {
+    let seed = playback.map_or_else(
+        || config.seed.unwrap_or_else(rand::random),
+        |p| p.replay.seed,
+    );
+    commands.insert_resource(GameRng(StdRng::seed_from_u64(seed)));
+    info!("Run seed is {}", seed);
+
+    if let Some(mut recorder) = recorder {
+        recorder.replay = Some(Replay::new(
+            seed,
+            players.0,
+            config.clone(),
+            loaded.map(|l| l.0.clone()),
+        ));
+    }
+}
+
+//pick this step's length and inputs, from the real world or from the replay being played
+//the first player is on the keyboard, the second on whichever gamepad was plugged in first
+#[allow(clippy::too_many_arguments)]
+pub fn step_frame(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    settings: Res<Settings>,
+    players: Res<PlayerCount>,
+    mut step: ResMut<StepTime>,
+    mut inputs: ResMut<PlayerInputs>,
+    mut integration: ResMut<IntegrationParameters>,
+    playback: Option<Res<Playback>>,
+) {
+    match playback {
+        Some(playback) => {
+            if let Some(frame) = playback.replay.frames.get(playback.frame) {
+                step.delta = Duration::from_secs_f32(frame.delta);
+                inputs.0 = frame.inputs.clone();
+            } else {
+                step.delta = Duration::ZERO;
+                inputs.0.clear();
+            }
+        }
+        None => {
+            step.delta = Duration::from_secs_f32(FIXED_STEP);
+            let gamepad = gamepads.iter().min_by_key(|g| g.0).copied();
+            inputs.0 = (0..players.0)
+                .map(|slot| match (slot, gamepad) {
+                    (0, _) => PlayerInput::from_keys(&keys, &settings.keys),
+                    (_, Some(gamepad)) => PlayerInput::from_gamepad(gamepad, &buttons, &axes),
+                    _ => PlayerInput::default(),
+                })
+                .collect();
+        }
+    }
+    //physics advances by exactly the same step as everything else
+    integration.dt = step.delta_seconds();
+}
+
+pub fn record_frame(
+    recorder: Option<ResMut<Recorder>>,
+    step: Res<StepTime>,
+    inputs: Res<PlayerInputs>,
+    world: WorldChecksum,
+) {
+    if let Some(replay) = recorder.and_then(|r| r.into_inner().replay.as_mut()) {
+        replay.frames.push(ReplayFrame {
+            delta: step.delta_seconds(),
+            inputs: inputs.0.clone(),
+            checksum: world.checksum(),
+        });
+    }
+}
+
+//compare against the recording and stop once it runs out
+pub fn check_frame(
+    playback: Option<ResMut<Playback>>,
+    world: WorldChecksum,
+    status: Res<ExitStatus>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let mut playback = match playback {
+        Some(p) => p,
+        None => return,
+    };
+
+    let frame = playback.frame;
+    match playback.replay.frames.get(frame) {
+        Some(recorded) => {
+            if playback.diverged.is_none() && recorded.checksum != world.checksum() {
+                error!("Replay diverged from the recording at frame {}", frame);
+                playback.diverged = Some(frame);
+            }
+            playback.frame += 1;
+        }
+        None => {
+            match playback.diverged {
+                Some(at) => {
+                    error!("Replay of {} frames diverged at frame {}", frame, at);
+                    status.fail(1);
+                }
+                None => info!("Replay of {} frames matched the recording", frame),
+            }
+            exit.send(AppExit);
+        }
+    }
+}
+
+//write out the recording when the run ends, either back to the menu or by quitting
+pub fn finish_recording(recorder: Option<ResMut<Recorder>>) {
+    if let Some(mut recorder) = recorder {
+        write_recording(&mut recorder);
+    }
+}
+
+pub fn finish_recording_on_exit(
+    mut ev_exit: EventReader<AppExit>,
+    recorder: Option<ResMut<Recorder>>,
+) {
+    if ev_exit.iter().next().is_some() {
+        finish_recording(recorder);
+    }
+}
+
+fn write_recording(recorder: &mut Recorder) {
+    if let Some(replay) = recorder.replay.take() {
+        match replay.write(&recorder.path) {
+            Ok(()) => info!(
+                "Wrote {} frame replay to {}",
+                replay.frames.len(),
+                recorder.path.display()
+            ),
+            Err(e) => error!("Failed to write replay: {:#}", e),
+        }
+    }
+}
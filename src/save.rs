@@ -0,0 +1,587 @@
+use anyhow::{bail, Context};
+use bevy::{ecs::system::SystemParam, prelude::*, window::WindowCloseRequested};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, time::Duration};
+
+use crate::{
+    bomb::Bombs,
+    enemies::EnemyRoster,
+    gameplay::{spawn_enemy, Enemy, EnemySpawn, Fireball, Player, Reticle},
+    hits::{Despawning, Hostile, Pierce},
+    items::Inventory,
+    movement::{Dash, Invulnerable, Stamina, VelocityModifiers},
+    patterns::{self, ActiveEmitter, Emitter, OnDeath, PendingPattern},
+    pickups::{spawn_pickup, Pickup, PickupTimers},
+    pool::EntityPool,
+    status::{ActiveStatus, Inflicts, StatusDef, Statuses},
+    ui::PlayerHP,
+    CurrentAttack, DifficultyTimer, EnemyTimer, FireballTimer, RunTime, Score, Wave,
+};
+
+//bump this whenever the layout of RunSnapshot changes, old saves are refused rather than misread
+pub const SAVE_VERSION: u32 = 10;
+pub const SAVE_PATH: &str = "savegame.ron";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TimerState {
+    pub elapsed: f32,
+    pub duration: f32,
+    pub paused: bool,
+}
+
+impl TimerState {
+    pub fn from_timer(timer: &Timer) -> TimerState {
+        TimerState {
+            elapsed: timer.elapsed_secs(),
+            duration: timer.duration().as_secs_f32(),
+            paused: timer.paused(),
+        }
+    }
+
+    pub fn to_timer(&self) -> Timer {
+        let mut timer = Timer::from_seconds(self.duration, true);
+        timer.set_elapsed(Duration::from_secs_f32(self.elapsed));
+        if self.paused {
+            timer.pause();
+        }
+        timer
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpawnerState {
+    pub position: Vec2,
+    pub timer: TimerState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerState {
+    pub position: Vec2,
+    //where their reticle is, it stays put while they aren't aiming
+    pub reticle: Vec2,
+    pub hp: i16,
+    pub attack: String,
+    pub fire_timer: TimerState,
+    //item ids, in the order they were picked up
+    pub items: Vec<String>,
+    pub dash: Dash,
+    pub stamina: Stamina,
+    pub modifiers: VelocityModifiers,
+    //seconds left of it, 0 when it can be hurt
+    pub invulnerable: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EnemyState {
+    pub position: Vec2,
+    //its name in the enemy roster
+    pub kind: String,
+    pub speed: f32,
+    pub health: f32,
+    pub statuses: Vec<ActiveStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProjectileState {
+    pub position: Vec2,
+    pub origin: Vec3,
+    pub target: Vec3,
+    pub speed: f32,
+    pub damage: f32,
+    pub on_death: Vec<Emitter>,
+    pub pierce: u32,
+    pub statuses: Vec<StatusDef>,
+    pub hostile: bool,
+    pub owner: usize,
+}
+
+//a pattern that was fired but whose asset hadn't loaded yet
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PatternState {
+    pub path: String,
+    pub origin: Vec3,
+    pub target: Vec3,
+    pub owner: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PickupState {
+    pub position: Vec2,
+    pub pickup: Pickup,
+}
+
+impl EnemyState {
+    //put the enemy back in the world as it was, one whose kind has gone from the roster looks like the default
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        pool: &mut EntityPool<Enemy>,
+        sprite: &Handle<TextureAtlas>,
+        roster: Option<&EnemyRoster>,
+    ) -> Entity {
+        let kind = roster
+            .and_then(|r| r.kind(&self.kind))
+            .cloned()
+            .unwrap_or_default();
+        let ent = spawn_enemy(
+            commands,
+            pool,
+            sprite,
+            Transform::from_translation(self.position.extend(0.0)),
+            Enemy {
+                kind: self.kind.clone(),
+                speed: self.speed,
+                health: self.health,
+            },
+            &kind,
+        );
+        if !self.statuses.is_empty() {
+            commands.entity(ent).insert(Statuses(self.statuses.clone()));
+        }
+        ent
+    }
+}
+
+impl ProjectileState {
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        pool: &mut EntityPool<Fireball>,
+        sprite: &Handle<TextureAtlas>,
+    ) -> Entity {
+        let ent = patterns::spawn_projectile(
+            commands,
+            pool,
+            sprite,
+            self.position.extend(0.0),
+            Fireball {
+                origin: self.origin,
+                target: self.target,
+                speed: self.speed,
+                damage: self.damage,
+                owner: self.owner,
+            },
+            &self.on_death,
+            self.pierce,
+            &self.statuses,
+        );
+        if self.hostile {
+            commands.entity(ent).insert(Hostile);
+        }
+        ent
+    }
+}
+
+impl PatternState {
+    pub fn fire(&self, commands: &mut Commands, asset_server: &AssetServer) {
+        patterns::fire_pattern(
+            commands,
+            &asset_server.load(self.path.as_str()),
+            self.origin,
+            self.target,
+            self.owner,
+        );
+    }
+}
+
+impl PickupState {
+    pub fn spawn(&self, commands: &mut Commands) {
+        spawn_pickup(commands, self.pickup.clone(), self.position);
+    }
+}
+
+//everything needed to pick a run back up where it was left
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RunSnapshot {
+    pub version: u32,
+    //by slot
+    pub players: Vec<PlayerState>,
+    pub score: u32,
+    pub bombs: u32,
+    pub wave: u32,
+    //seconds played
+    pub run_time: f32,
+    pub difficulty: TimerState,
+    pub spawners: Vec<SpawnerState>,
+    pub enemies: Vec<EnemyState>,
+    pub projectiles: Vec<ProjectileState>,
+    //emitters still firing their volleys, and patterns waiting to become them
+    pub emitters: Vec<ActiveEmitter>,
+    pub patterns: Vec<PatternState>,
+    //bombs and items lying on the field, and the countdowns to the next of each
+    pub pickups: Vec<PickupState>,
+    pub bomb_pickup: TimerState,
+    pub item_pickup: TimerState,
+}
+
+//a snapshot picked from the main menu, consumed when the run starts
+pub struct LoadedRun(pub RunSnapshot);
+
+pub fn save_exists() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+pub fn write_snapshot(snapshot: &RunSnapshot, path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let text = ron::ser::to_string_pretty(snapshot, ron::ser::PrettyConfig::default())?;
+
+    //write next to the real file first so a crash mid-write can't eat the old save
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, text).with_context(|| format!("Unable to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Unable to replace {}", path.display()))?;
+    Ok(())
+}
+
+pub fn read_snapshot(path: impl AsRef<Path>) -> anyhow::Result<RunSnapshot> {
+    let path = path.as_ref();
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let snapshot: RunSnapshot =
+        ron::from_str(&text).with_context(|| format!("{} is not a valid save", path.display()))?;
+
+    if snapshot.version != SAVE_VERSION {
+        bail!(
+            "{} is save version {}, this build reads version {}",
+            path.display(),
+            snapshot.version,
+            SAVE_VERSION
+        );
+    }
+    Ok(snapshot)
+}
+
+//read access to everything that goes into a snapshot
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+pub struct RunState<'w, 's> {
+    players: Query<
+        'w,
+        's,
+        (
+            &'static Player,
+            &'static Transform,
+            &'static PlayerHP,
+            &'static CurrentAttack,
+            &'static FireballTimer,
+            Option<&'static Inventory>,
+            (
+                &'static Dash,
+                &'static Stamina,
+                &'static VelocityModifiers,
+                Option<&'static Invulnerable>,
+            ),
+        ),
+    >,
+    reticles: Query<'w, 's, (&'static Reticle, &'static Transform), Without<Player>>,
+    score: Res<'w, Score>,
+    bombs: Res<'w, Bombs>,
+    wave: Res<'w, Wave>,
+    run_time: Res<'w, RunTime>,
+    difficulty: Res<'w, DifficultyTimer>,
+    spawners: Query<'w, 's, (&'static Transform, &'static EnemyTimer), With<EnemySpawn>>,
+    enemies: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Enemy,
+            Option<&'static Statuses>,
+        ),
+        Without<Despawning>,
+    >,
+    projectiles: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Fireball,
+            Option<&'static OnDeath>,
+            Option<&'static Pierce>,
+            Option<&'static Inflicts>,
+            Option<&'static Hostile>,
+        ),
+        Without<Despawning>,
+    >,
+    emitters: Query<'w, 's, &'static ActiveEmitter>,
+    patterns: Query<'w, 's, &'static PendingPattern>,
+    asset_server: Res<'w, AssetServer>,
+    pickups: Query<'w, 's, (&'static Transform, &'static Pickup)>,
+    pickup_timers: Res<'w, PickupTimers>,
+}
+
+impl<'w, 's> RunState<'w, 's> {
+    pub fn snapshot(&self) -> Option<RunSnapshot> {
+        let mut players: Vec<_> = self.players.iter().collect();
+        if players.is_empty() {
+            return None;
+        }
+        players.sort_by_key(|(p, ..)| p.slot);
+
+        Some(RunSnapshot {
+            version: SAVE_VERSION,
+            players: players
+                .into_iter()
+                .map(
+                    |(player, tr, hp, attack, fire_timer, inventory, movement)| {
+                        let (dash, stamina, modifiers, invulnerable) = movement;
+                        let reticle = self
+                            .reticles
+                            .iter()
+                            .find(|(r, _)| r.0 == player.slot)
+                            .map_or(tr.translation, |(_, r)| r.translation);
+                        PlayerState {
+                            position: tr.translation.truncate(),
+                            reticle: reticle.truncate(),
+                            hp: hp.0,
+                            attack: attack.0.name().to_string(),
+                            fire_timer: TimerState::from_timer(&fire_timer.0),
+                            items: inventory.map(|i| i.items.clone()).unwrap_or_default(),
+                            dash: dash.clone(),
+                            stamina: stamina.clone(),
+                            modifiers: modifiers.clone(),
+                            invulnerable: invulnerable.map_or(0.0, |i| i.0),
+                        }
+                    },
+                )
+                .collect(),
+            score: self.score.0,
+            bombs: self.bombs.0,
+            wave: self.wave.0,
+            run_time: self.run_time.0,
+            difficulty: TimerState::from_timer(&self.difficulty.0),
+            spawners: self
+                .spawners
+                .iter()
+                .map(|(tr, timer)| SpawnerState {
+                    position: tr.translation.truncate(),
+                    timer: TimerState::from_timer(&timer.0),
+                })
+                .collect(),
+            enemies: self
+                .enemies
+                .iter()
+                .map(|(tr, enemy, statuses)| EnemyState {
+                    position: tr.translation.truncate(),
+                    kind: enemy.kind.clone(),
+                    speed: enemy.speed,
+                    health: enemy.health,
+                    statuses: statuses.map(|s| s.0.clone()).unwrap_or_default(),
+                })
+                .collect(),
+            projectiles: self
+                .projectiles
+                .iter()
+                .map(
+                    |(tr, ball, on_death, pierce, inflicts, hostile)| ProjectileState {
+                        position: tr.translation.truncate(),
+                        origin: ball.origin,
+                        target: ball.target,
+                        speed: ball.speed,
+                        damage: ball.damage,
+                        on_death: on_death.map(|o| o.0.clone()).unwrap_or_default(),
+                        pierce: pierce.map_or(0, |p| p.left),
+                        statuses: inflicts.map(|i| i.0.clone()).unwrap_or_default(),
+                        hostile: hostile.is_some(),
+                        owner: ball.owner,
+                    },
+                )
+                .collect(),
+            emitters: self.emitters.iter().cloned().collect(),
+            patterns: self
+                .patterns
+                .iter()
+                .filter_map(|p| {
+                    Some(PatternState {
+                        path: self
+                            .asset_server
+                            .get_handle_path(&p.pattern)?
+                            .path()
+                            .to_str()?
+                            .to_string(),
+                        origin: p.origin,
+                        target: p.target,
+                        owner: p.owner,
+                    })
+                })
+                .collect(),
+            pickups: self
+                .pickups
+                .iter()
+                .map(|(tr, pickup)| PickupState {
+                    position: tr.translation.truncate(),
+                    pickup: pickup.clone(),
+                })
+                .collect(),
+            bomb_pickup: TimerState::from_timer(&self.pickup_timers.bomb),
+            item_pickup: TimerState::from_timer(&self.pickup_timers.item),
+        })
+    }
+
+    pub fn save(&self) {
+        let snapshot = match self.snapshot() {
+            Some(s) => s,
+            None => return,
+        };
+        match write_snapshot(&snapshot, SAVE_PATH) {
+            Ok(()) => info!("Saved run to {}", SAVE_PATH),
+            Err(e) => error!("Failed to save run: {:#}", e),
+        }
+    }
+}
+
+//--systems--//
+
+pub fn save_run(run: RunState) {
+    run.save();
+}
+
+//closing the window mid-run shouldn't lose it
+pub fn save_on_close(mut ev_close: EventReader<WindowCloseRequested>, run: RunState) {
+    if ev_close.iter().next().is_some() {
+        run.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        movement::{Modifier, ModifierSource},
+        status::StatusKind,
+    };
+    use std::path::PathBuf;
+
+    //somewhere of its own for each test to write to
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("game_thing_{}_{}.ron", name, std::process::id()))
+    }
+
+    fn snapshot() -> RunSnapshot {
+        let mut modifiers = VelocityModifiers::default();
+        modifiers.add(
+            ModifierSource::Knockback,
+            Modifier::Push(Vec2::new(-600.0, 0.0)),
+            Some(0.1),
+        );
+        let mut fire_timer = Timer::from_seconds(0.1, true);
+        fire_timer.pause();
+        let burning = StatusDef {
+            kind: StatusKind::Burn,
+            duration: 2.0,
+            interval: 0.5,
+            strength: 1.0,
+        };
+        RunSnapshot {
+            version: SAVE_VERSION,
+            players: vec![PlayerState {
+                position: Vec2::new(12.5, -40.0),
+                reticle: Vec2::new(112.5, -40.0),
+                hp: 2,
+                attack: "patterns/split.pattern.ron".to_string(),
+                fire_timer: TimerState::from_timer(&fire_timer),
+                items: vec!["boots".to_string()],
+                dash: Dash::default(),
+                stamina: Stamina::new(100.0),
+                modifiers,
+                invulnerable: 0.4,
+            }],
+            score: 17,
+            bombs: 2,
+            wave: 3,
+            run_time: 102.5,
+            difficulty: TimerState {
+                elapsed: 12.0,
+                duration: 30.0,
+                paused: false,
+            },
+            spawners: vec![SpawnerState {
+                position: Vec2::new(540.0, 260.0),
+                timer: TimerState {
+                    elapsed: 0.75,
+                    duration: 1.5,
+                    paused: false,
+                },
+            }],
+            enemies: vec![EnemyState {
+                position: Vec2::new(-100.0, 30.0),
+                kind: "walker".to_string(),
+                speed: 175.0,
+                health: 2.0,
+                statuses: vec![ActiveStatus {
+                    kind: StatusKind::Slow,
+                    left: 1.0,
+                    interval: 0.05,
+                    strength: 0.5,
+                    next_tick: 0.05,
+                }],
+            }],
+            projectiles: vec![ProjectileState {
+                position: Vec2::new(5.0, 5.0),
+                origin: Vec3::ZERO,
+                target: Vec3::new(100.0, 0.0, 0.0),
+                speed: 500.0,
+                damage: 1.0,
+                on_death: vec![Emitter::default()],
+                pierce: 1,
+                statuses: vec![burning],
+                hostile: true,
+                owner: 1,
+            }],
+            emitters: vec![ActiveEmitter::new(
+                Emitter::default(),
+                Vec3::new(1.0, 2.0, 0.0),
+                Vec2::X,
+                0,
+            )],
+            patterns: vec![PatternState {
+                path: "patterns/basic.pattern.ron".to_string(),
+                origin: Vec3::ZERO,
+                target: Vec3::Y,
+                owner: 0,
+            }],
+            pickups: vec![PickupState {
+                position: Vec2::new(-200.0, 100.0),
+                pickup: Pickup::Item("boots".to_string()),
+            }],
+            bomb_pickup: TimerState {
+                elapsed: 4.0,
+                duration: 20.0,
+                paused: false,
+            },
+            item_pickup: TimerState {
+                elapsed: 30.0,
+                duration: 45.0,
+                paused: false,
+            },
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let path = temp_path("round_trip");
+        let saved = snapshot();
+        write_snapshot(&saved, &path).unwrap();
+        let loaded = read_snapshot(&path);
+        fs::remove_file(&path).ok();
+        assert_eq!(loaded.unwrap(), saved);
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let path = temp_path("old_version");
+        let old = RunSnapshot {
+            version: SAVE_VERSION - 1,
+            ..snapshot()
+        };
+        write_snapshot(&old, &path).unwrap();
+        let loaded = read_snapshot(&path);
+        fs::remove_file(&path).ok();
+        let err = loaded.unwrap_err().to_string();
+        assert!(
+            err.contains(&format!("save version {}", SAVE_VERSION - 1)),
+            "{}",
+            err
+        );
+    }
+}
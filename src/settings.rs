@@ -0,0 +1,270 @@
+use anyhow::Context;
+use bevy::{prelude::*, window::WindowMode};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+pub const SETTINGS_PATH: &str = "settings.ron";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum DisplayMode {
+    Windowed,
+    Resizable,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    pub fn next(&self) -> DisplayMode {
+        match self {
+            DisplayMode::Windowed => DisplayMode::Resizable,
+            DisplayMode::Resizable => DisplayMode::Fullscreen,
+            DisplayMode::Fullscreen => DisplayMode::Windowed,
+        }
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        match self {
+            DisplayMode::Fullscreen => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        }
+    }
+
+    pub fn resizable(&self) -> bool {
+        *self == DisplayMode::Resizable
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DisplayMode::Windowed => "Windowed",
+            DisplayMode::Resizable => "Resizable",
+            DisplayMode::Fullscreen => "Fullscreen",
+        }
+    }
+}
+
+//everything a key can be bound to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Sprint,
+    Dash,
+    Bomb,
+    FireUp,
+    FireDown,
+    FireLeft,
+    FireRight,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Sprint,
+        Action::Dash,
+        Action::Bomb,
+        Action::FireUp,
+        Action::FireDown,
+        Action::FireLeft,
+        Action::FireRight,
+        Action::Pause,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Up => "Move Up",
+            Action::Down => "Move Down",
+            Action::Left => "Move Left",
+            Action::Right => "Move Right",
+            Action::Sprint => "Sprint",
+            Action::Dash => "Dash",
+            Action::Bomb => "Bomb",
+            Action::FireUp => "Fire Up",
+            Action::FireDown => "Fire Down",
+            Action::FireLeft => "Fire Left",
+            Action::FireRight => "Fire Right",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub sprint: KeyCode,
+    pub dash: KeyCode,
+    pub bomb: KeyCode,
+    pub fire_up: KeyCode,
+    pub fire_down: KeyCode,
+    pub fire_left: KeyCode,
+    pub fire_right: KeyCode,
+    pub pause: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            up: KeyCode::W,
+            down: KeyCode::S,
+            left: KeyCode::A,
+            right: KeyCode::D,
+            sprint: KeyCode::LShift,
+            dash: KeyCode::Space,
+            bomb: KeyCode::E,
+            fire_up: KeyCode::Up,
+            fire_down: KeyCode::Down,
+            fire_left: KeyCode::Left,
+            fire_right: KeyCode::Right,
+            pause: KeyCode::Escape,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, action: Action) -> KeyCode {
+        match action {
+            Action::Up => self.up,
+            Action::Down => self.down,
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::Sprint => self.sprint,
+            Action::Dash => self.dash,
+            Action::Bomb => self.bomb,
+            Action::FireUp => self.fire_up,
+            Action::FireDown => self.fire_down,
+            Action::FireLeft => self.fire_left,
+            Action::FireRight => self.fire_right,
+            Action::Pause => self.pause,
+        }
+    }
+
+    //a key can only do one thing, so whatever already had it gets this action's old key
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        let old = self.key(action);
+        if let Some(other) = Action::ALL.iter().find(|a| self.key(**a) == key) {
+            *self.slot(*other) = old;
+        }
+        *self.slot(action) = key;
+    }
+
+    pub fn pressed(&self, input: &Input<KeyCode>, action: Action) -> bool {
+        input.pressed(self.key(action))
+    }
+
+    fn slot(&mut self, action: Action) -> &mut KeyCode {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Down => &mut self.down,
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Sprint => &mut self.sprint,
+            Action::Dash => &mut self.dash,
+            Action::Bomb => &mut self.bomb,
+            Action::FireUp => &mut self.fire_up,
+            Action::FireDown => &mut self.fire_down,
+            Action::FireLeft => &mut self.fire_left,
+            Action::FireRight => &mut self.fire_right,
+            Action::Pause => &mut self.pause,
+        }
+    }
+}
+
+//player facing options, changed from the options menu and kept in settings.ron
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    //volumes go from 0 to 1, music and sfx are scaled by master
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub display: DisplayMode,
+    pub vsync: bool,
+    pub keys: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.0,
+            music_volume: 0.7,
+            sfx_volume: 1.0,
+            display: DisplayMode::Windowed,
+            vsync: true,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    //a missing file is just a first run, a broken one shouldn't stop the game starting
+    pub fn load() -> Settings {
+        if !Path::new(SETTINGS_PATH).exists() {
+            return Settings::default();
+        }
+        match Settings::read(SETTINGS_PATH) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Using default settings: {:#}", e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Settings> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        ron::from_str(&text)
+            .with_context(|| format!("{} is not a valid settings file", path.display()))
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.master_volume * self.music_volume
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.master_volume * self.sfx_volume
+    }
+}
+
+//--systems--//
+
+pub fn save_settings(settings: Res<Settings>) {
+    match settings.write(SETTINGS_PATH) {
+        Ok(()) => info!("Saved settings to {}", SETTINGS_PATH),
+        Err(e) => error!("Failed to save settings: {:#}", e),
+    }
+}
+
+//push display changes to the window as soon as they're made
+pub fn apply_display(settings: Res<Settings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Some(window) = windows.get_primary_mut() {
+        if window.mode() != settings.display.window_mode() {
+            window.set_mode(settings.display.window_mode());
+        }
+        if window.resizable() != settings.display.resizable() {
+            window.set_resizable(settings.display.resizable());
+        }
+        if window.vsync() != settings.vsync {
+            window.set_vsync(settings.vsync);
+        }
+    }
+}
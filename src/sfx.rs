@@ -0,0 +1,189 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadState, LoadedAsset},
+    audio::AudioSource,
+    prelude::*,
+    reflect::TypeUuid,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::{audio::Mixer, settings::Settings};
+
+//everything in the game that makes a noise, sent as an event by whatever caused it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SoundEvent {
+    Shoot,
+    EnemyDeath,
+    PlayerHit,
+    Pickup,
+    LevelUp,
+    Bomb,
+}
+
+//how one event sounds
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SoundDef {
+    //path under assets
+    pub file: String,
+    //scaled by the sfx volume from the options menu
+    pub volume: f32,
+    //seconds before it can play again, events sent in between are dropped
+    pub cooldown: f32,
+    //how many copies can be playing at once
+    pub voices: usize,
+}
+
+impl Default for SoundDef {
+    fn default() -> Self {
+        SoundDef {
+            file: String::new(),
+            volume: 1.0,
+            cooldown: 0.0,
+            voices: 4,
+        }
+    }
+}
+
+//which sound goes with which event, authored as a .sounds.ron file in assets/audio
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "b6a2e0c4-3f19-4d8e-8e27-6c5a91d4f0b2"]
+pub struct SoundBank {
+    pub sounds: HashMap<SoundEvent, SoundDef>,
+}
+
+#[derive(Default)]
+pub struct SoundBankLoader;
+
+impl AssetLoader for SoundBankLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let bank = ron::de::from_bytes::<SoundBank>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(bank));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sounds.ron"]
+    }
+}
+
+//--resources--//
+
+//the loaded bank and the clips it points at
+pub struct Sfx {
+    bank: Handle<SoundBank>,
+    clips: HashMap<SoundEvent, Handle<AudioSource>>,
+    //when each sound last started, in seconds since startup
+    last_played: HashMap<SoundEvent, f64>,
+    //sounds that failed to load, so the warning only shows once
+    missing: HashSet<SoundEvent>,
+}
+
+impl Sfx {
+    pub fn new(bank: Handle<SoundBank>) -> Sfx {
+        Sfx {
+            bank,
+            clips: HashMap::new(),
+            last_played: HashMap::new(),
+            missing: HashSet::new(),
+        }
+    }
+}
+
+//--systems--//
+
+//start loading the clips once the bank is in, and again whenever it's edited
+pub fn load_clips(
+    mut sfx: ResMut<Sfx>,
+    mut events: EventReader<AssetEvent<SoundBank>>,
+    banks: Res<Assets<SoundBank>>,
+    asset_server: Res<AssetServer>,
+) {
+    for ev in events.iter() {
+        let handle = match ev {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != sfx.bank {
+            continue;
+        }
+        if let Some(bank) = banks.get(handle) {
+            let clips = bank
+                .sounds
+                .iter()
+                .map(|(event, def)| (*event, asset_server.load(def.file.as_str())))
+                .collect();
+            sfx.clips = clips;
+            sfx.missing.clear();
+            info!("Loaded {} sounds", bank.sounds.len());
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_sfx(
+    mut events: EventReader<SoundEvent>,
+    mut mixer: NonSendMut<Mixer>,
+    mut sfx: ResMut<Sfx>,
+    banks: Res<Assets<SoundBank>>,
+    sources: Res<Assets<AudioSource>>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    //without a bank everything stays quiet, the asset server already said why
+    let bank = match banks.get(&sfx.bank) {
+        Some(b) => b,
+        None => return,
+    };
+    let now = time.seconds_since_startup();
+
+    //several of the same event in one frame only need to play once
+    let mut heard = HashSet::new();
+    for event in events.iter().copied() {
+        if !heard.insert(event) {
+            continue;
+        }
+        let def = match bank.sounds.get(&event) {
+            Some(d) => d,
+            None => continue,
+        };
+        let clip = match sfx.clips.get(&event) {
+            Some(c) => c.clone(),
+            None => continue,
+        };
+        let source = match sources.get(&clip) {
+            Some(s) => s,
+            None => {
+                if asset_server.get_load_state(&clip) == LoadState::Failed
+                    && sfx.missing.insert(event)
+                {
+                    warn!("No sound for {:?}, {} didn't load", event, def.file);
+                }
+                continue;
+            }
+        };
+        if sfx
+            .last_played
+            .get(&event)
+            .is_some_and(|last| now - last < def.cooldown as f64)
+        {
+            continue;
+        }
+        if mixer.play_sfx(
+            event,
+            source,
+            def.volume * settings.sfx_volume(),
+            def.voices,
+        ) {
+            sfx.last_played.insert(event, now);
+        }
+    }
+}
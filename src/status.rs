@@ -0,0 +1,287 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    gameplay::Enemy,
+    hits::{Despawning, HitOutcomes},
+    StepTime,
+};
+
+//poison is the only one that stacks, this many at once at most
+pub const MAX_POISON_STACKS: usize = 5;
+//damage ticks never come faster than this, however the effect was authored
+const MIN_INTERVAL: f32 = 0.05;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+    //damage every interval
+    Burn,
+    //speed is multiplied by strength
+    Slow,
+    //can't move at all
+    Freeze,
+    //damage every interval for each stack
+    Poison,
+}
+
+impl StatusKind {
+    fn damages(&self) -> bool {
+        matches!(self, StatusKind::Burn | StatusKind::Poison)
+    }
+
+    //tint for a sprite carrying it, the first one in this order wins
+    fn color(&self) -> Color {
+        match self {
+            StatusKind::Freeze => Color::rgb(0.6, 0.9, 1.0),
+            StatusKind::Burn => Color::rgb(1.0, 0.55, 0.2),
+            StatusKind::Poison => Color::rgb(0.5, 1.0, 0.4),
+            StatusKind::Slow => Color::rgb(0.5, 0.6, 1.0),
+        }
+    }
+}
+
+//a status effect as authored in a pattern, times are in seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StatusDef {
+    pub kind: StatusKind,
+    pub duration: f32,
+    //seconds between damage ticks, only used by burn and poison
+    #[serde(default)]
+    pub interval: f32,
+    //damage per tick, or the speed multiplier for slow
+    #[serde(default)]
+    pub strength: f32,
+}
+
+//one running effect, or one stack of poison
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ActiveStatus {
+    pub kind: StatusKind,
+    pub left: f32,
+    pub interval: f32,
+    pub strength: f32,
+    //counts down to the next damage tick
+    pub next_tick: f32,
+}
+
+impl ActiveStatus {
+    fn new(def: &StatusDef) -> ActiveStatus {
+        let interval = def.interval.max(MIN_INTERVAL);
+        ActiveStatus {
+            kind: def.kind,
+            left: def.duration,
+            interval,
+            strength: def.strength,
+            next_tick: interval,
+        }
+    }
+}
+
+//--components--//
+
+//status effects a projectile puts on whatever it hits
+#[derive(Component)]
+pub struct Inflicts(pub Vec<StatusDef>);
+
+//every status effect on an entity
+#[derive(Component, Default)]
+pub struct Statuses(pub Vec<ActiveStatus>);
+
+impl Statuses {
+    pub fn from_defs(defs: &[StatusDef]) -> Statuses {
+        let mut statuses = Statuses::default();
+        for def in defs.iter() {
+            statuses.apply(def);
+        }
+        statuses
+    }
+
+    //poison adds a stack, pushing out the one closest to running out when full,
+    //anything else already running is topped back up and keeps the stronger strength
+    pub fn apply(&mut self, def: &StatusDef) {
+        let new = ActiveStatus::new(def);
+        if def.kind == StatusKind::Poison {
+            if self.stacks(StatusKind::Poison) >= MAX_POISON_STACKS {
+                let oldest = self
+                    .0
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, s)| s.kind == StatusKind::Poison)
+                    .min_by(|(_, a), (_, b)| a.left.total_cmp(&b.left))
+                    .map(|(i, _)| i);
+                if let Some(i) = oldest {
+                    self.0.remove(i);
+                }
+            }
+            self.0.push(new);
+            return;
+        }
+
+        match self.0.iter_mut().find(|s| s.kind == def.kind) {
+            Some(running) => {
+                running.left = running.left.max(new.left);
+                running.strength = match def.kind {
+                    StatusKind::Slow => running.strength.min(new.strength),
+                    _ => running.strength.max(new.strength),
+                };
+            }
+            None => self.0.push(new),
+        }
+    }
+
+    pub fn stacks(&self, kind: StatusKind) -> usize {
+        self.0.iter().filter(|s| s.kind == kind).count()
+    }
+
+    //run every effect forward, dropping the ones that ran out, and return the damage they did
+    pub fn tick(&mut self, delta: f32) -> f32 {
+        let mut damage = 0.0;
+        for status in self.0.iter_mut() {
+            //a tick due right as it runs out still lands
+            let ran = delta.min(status.left.max(0.0));
+            status.left -= delta;
+            if !status.kind.damages() {
+                continue;
+            }
+            status.next_tick -= ran;
+            while status.next_tick <= 0.0 {
+                damage += status.strength;
+                status.next_tick += status.interval;
+            }
+        }
+        self.0.retain(|s| s.left > 0.0);
+        damage
+    }
+
+    //what movement speed gets multiplied by
+    pub fn speed_scale(&self) -> f32 {
+        if self.stacks(StatusKind::Freeze) > 0 {
+            return 0.0;
+        }
+        self.0
+            .iter()
+            .filter(|s| s.kind == StatusKind::Slow)
+            .map(|s| s.strength.clamp(0.0, 1.0))
+            .fold(1.0, f32::min)
+    }
+
+    pub fn tint(&self) -> Option<Color> {
+        [
+            StatusKind::Freeze,
+            StatusKind::Burn,
+            StatusKind::Poison,
+            StatusKind::Slow,
+        ]
+        .iter()
+        .find(|kind| self.stacks(**kind) > 0)
+        .map(|kind| kind.color())
+    }
+}
+
+//--systems--//
+
+//damage over time, after hits. what a hit killed this step isn't marked Despawning until the stage's
+//commands are applied, so it's the health check that keeps it from being killed again
+pub fn tick_statuses(
+    mut commands: Commands,
+    time: Res<StepTime>,
+    mut q: Query<(Entity, &Transform, &mut Statuses, Option<&mut Enemy>), Without<Despawning>>,
+    mut outcomes: HitOutcomes,
+) {
+    for (ent, tr, mut statuses, enemy) in q.iter_mut() {
+        let damage = statuses.tick(time.delta_seconds());
+        let mut enemy = match enemy {
+            Some(e) if damage > 0.0 && e.health > 0.0 => e,
+            _ => continue,
+        };
+        outcomes.hurt(
+            &mut commands,
+            ent,
+            &mut enemy,
+            tr.translation,
+            damage,
+            Vec2::ZERO,
+            None,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(kind: StatusKind, duration: f32, strength: f32) -> StatusDef {
+        StatusDef {
+            kind,
+            duration,
+            interval: 0.5,
+            strength,
+        }
+    }
+
+    #[test]
+    fn poison_stacks_up_to_the_cap() {
+        let mut statuses = Statuses::default();
+        for i in 0..MAX_POISON_STACKS + 2 {
+            statuses.apply(&def(StatusKind::Poison, 1.0 + i as f32, 1.0));
+        }
+        assert_eq!(statuses.stacks(StatusKind::Poison), MAX_POISON_STACKS);
+        //the ones closest to running out were pushed out
+        let shortest = statuses.0.iter().map(|s| s.left).fold(f32::MAX, f32::min);
+        assert_eq!(shortest, 3.0);
+    }
+
+    #[test]
+    fn reapplying_refreshes_instead_of_stacking() {
+        let mut statuses = Statuses::default();
+        statuses.apply(&def(StatusKind::Burn, 2.0, 1.0));
+        statuses.tick(1.5);
+        statuses.apply(&def(StatusKind::Burn, 2.0, 3.0));
+        assert_eq!(statuses.stacks(StatusKind::Burn), 1);
+        assert_eq!(statuses.0[0].left, 2.0);
+        assert_eq!(statuses.0[0].strength, 3.0);
+
+        //a shorter one doesn't cut it down
+        statuses.apply(&def(StatusKind::Burn, 0.5, 1.0));
+        assert_eq!(statuses.0[0].left, 2.0);
+        assert_eq!(statuses.0[0].strength, 3.0);
+    }
+
+    #[test]
+    fn statuses_expire_after_ticking() {
+        let mut statuses = Statuses::from_defs(&[
+            def(StatusKind::Burn, 1.0, 2.0),
+            def(StatusKind::Freeze, 0.25, 0.0),
+        ]);
+        assert_eq!(statuses.tick(0.5), 2.0);
+        assert_eq!(statuses.stacks(StatusKind::Freeze), 0);
+        assert_eq!(statuses.stacks(StatusKind::Burn), 1);
+
+        //the tick due right as it runs out still lands
+        assert_eq!(statuses.tick(0.5), 2.0);
+        assert!(statuses.0.is_empty());
+        assert_eq!(statuses.tick(0.5), 0.0);
+    }
+
+    #[test]
+    fn speed_scale_combines_slow_with_the_rest() {
+        let mut statuses = Statuses::default();
+        assert_eq!(statuses.speed_scale(), 1.0);
+
+        statuses.apply(&def(StatusKind::Slow, 2.0, 0.5));
+        statuses.apply(&def(StatusKind::Burn, 2.0, 1.0));
+        statuses.apply(&def(StatusKind::Poison, 2.0, 1.0));
+        assert_eq!(statuses.speed_scale(), 0.5);
+
+        //the stronger slow wins
+        statuses.apply(&def(StatusKind::Slow, 2.0, 0.25));
+        statuses.apply(&def(StatusKind::Slow, 2.0, 0.75));
+        assert_eq!(statuses.speed_scale(), 0.25);
+
+        //freezing stops it whatever else is on it, and it moves again once the freeze is gone
+        statuses.apply(&def(StatusKind::Freeze, 0.5, 0.0));
+        assert_eq!(statuses.speed_scale(), 0.0);
+        statuses.tick(1.0);
+        assert_eq!(statuses.speed_scale(), 0.25);
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::{app::Events, ecs::schedule::ShouldRun, prelude::*};
+use bevy_rapier2d::{
+    physics::{self, PhysicsStages, PhysicsSystems},
+    prelude::*,
+};
+
+use crate::net::NetSession;
+
+//gameplay and physics always advance by exactly this much, however fast frames come in
+pub const FIXED_STEP: f32 = 1.0 / 60.0;
+//never try to catch up on more than this much time at once, so one long hitch doesn't snowball
+const MAX_CATCH_UP: f32 = 0.25;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct FixedUpdate;
+
+//rapier's RapierPhysicsPlugin without the world step it adds to CoreStage::Update,
+//the fixed stage steps it instead so physics moves with gameplay and nowhere else
+pub struct FixedPhysicsPlugin;
+
+impl Plugin for FixedPhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_stage_before(
+            CoreStage::PreUpdate,
+            PhysicsStages::FinalizeCreations,
+            SystemStage::parallel(),
+        )
+        .add_stage_before(
+            CoreStage::PostUpdate,
+            PhysicsStages::SyncTransforms,
+            SystemStage::parallel(),
+        )
+        .insert_resource(PhysicsPipeline::new())
+        .insert_resource(QueryPipeline::new())
+        .insert_resource(RapierConfiguration::default())
+        .insert_resource(IntegrationParameters::default())
+        .insert_resource(BroadPhase::new())
+        .insert_resource(NarrowPhase::new())
+        .insert_resource(IslandManager::new())
+        .insert_resource(ImpulseJointSet::new())
+        .insert_resource(MultibodyJointSet::new())
+        .insert_resource(CCDSolver::new())
+        .insert_resource(Events::<IntersectionEvent>::default())
+        .insert_resource(Events::<ContactEvent>::default())
+        .insert_resource(physics::SimulationToRenderTime::default())
+        .insert_resource(physics::JointsEntityMap::default())
+        .insert_resource(physics::ModificationTracker::default())
+        .insert_resource(PhysicsHooksWithQueryObject::<NoUserData>(Box::new(())))
+        .add_system_to_stage(
+            PhysicsStages::FinalizeCreations,
+            physics::attach_bodies_and_colliders_system
+                .label(PhysicsSystems::AttachBodiesAndColliders),
+        )
+        .add_system_to_stage(
+            PhysicsStages::FinalizeCreations,
+            physics::create_joints_system.label(PhysicsSystems::CreateJoints),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            physics::finalize_collider_attach_to_bodies
+                .label(PhysicsSystems::FinalizeColliderAttachToBodies),
+        )
+        .add_system_to_stage(
+            PhysicsStages::SyncTransforms,
+            physics::sync_transforms.label(PhysicsSystems::SyncTransforms),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            physics::collect_removals.label(PhysicsSystems::CollectRemovals),
+        );
+    }
+}
+
+//time that has passed but hasn't been stepped through yet
+#[derive(Default)]
+pub struct FixedClock {
+    accumulator: f32,
+    looping: bool,
+    //take one step a frame as fast as frames come instead of keeping to the wall clock
+    pub unpaced: bool,
+}
+
+impl FixedClock {
+    //how far the frame is between the last step and the next one
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / FIXED_STEP
+    }
+}
+
+//keeps the simulated position of anything that moves, the Transform only holds it during a step
+#[derive(Component)]
+pub struct Interpolated {
+    prev: Vec3,
+    current: Vec3,
+}
+
+impl Interpolated {
+    pub fn new(position: Vec3) -> Interpolated {
+        Interpolated {
+            prev: position,
+            current: position,
+        }
+    }
+}
+
+//run criteria for the fixed stage, runs it as many times as the elapsed time calls for
+pub fn fixed_step(
+    time: Res<Time>,
+    mut clock: ResMut<FixedClock>,
+    net: Option<Res<NetSession>>,
+) -> ShouldRun {
+    if !clock.looping {
+        clock.accumulator += if clock.unpaced {
+            FIXED_STEP
+        } else {
+            time.delta_seconds()
+        };
+        clock.accumulator = clock.accumulator.min(MAX_CATCH_UP);
+    }
+
+    //a networked run waits on the other player's input, and catches back up once it's in
+    let waiting = net.is_some_and(|n| !n.ready());
+    if clock.accumulator >= FIXED_STEP && !waiting {
+        clock.accumulator -= FIXED_STEP;
+        clock.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        clock.looping = false;
+        ShouldRun::No
+    }
+}
+
+//--systems--//
+
+//put the simulated positions back before stepping
+pub fn begin_tick(mut q: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut tr, mut interp) in q.iter_mut() {
+        tr.translation = interp.current;
+        interp.prev = interp.current;
+    }
+}
+
+pub fn end_tick(mut q: Query<(&Transform, &mut Interpolated)>) {
+    for (tr, mut interp) in q.iter_mut() {
+        interp.current = tr.translation;
+    }
+}
+
+//draw everything part way between its last two steps
+pub fn interpolate(clock: Res<FixedClock>, mut q: Query<(&mut Transform, &Interpolated)>) {
+    let alpha = clock.alpha();
+    for (mut tr, interp) in q.iter_mut() {
+        tr.translation = interp.prev.lerp(interp.current, alpha);
+    }
+}
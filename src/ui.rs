@@ -1,9 +1,55 @@
 use bevy::prelude::*;
-use log::{debug, error, info, trace, warn};
+use log::{error, info};
 
-use crate::{Index, PlayerHitEvent};
+use crate::{
+    animation::{self, Animation, AnimationFinished},
+    bomb,
+    config::GameConfig,
+    gameplay::Player,
+    loading::AssetManifest,
+    movement::{Dash, Stamina},
+    patterns::BulletPattern,
+    sfx::SoundEvent,
+    CurrentAttack, PlayerHitEvent, RunTime, Score, Wave,
+};
 
-pub struct PlayerHP(i16);
+const FULL_COLOR: Color = Color::rgb(0.6, 0.8, 1.0);
+const STAMINA_COLOR: Color = Color::rgb(0.5, 0.9, 0.4);
+//not enough to do what the meter is for yet
+const LOW_COLOR: Color = Color::rgb(0.35, 0.35, 0.45);
+const METER_BACK: Color = Color::rgba(0.1, 0.1, 0.15, 0.6);
+const COMBO_COLOR: Color = Color::rgb(1.0, 0.75, 0.3);
+//heart.png is the full and empty heart side by side, drawn at twice its size
+const HEART_SIZE: f32 = 32.0;
+//kills closer together than this keep a combo going
+const COMBO_TIME: f32 = 2.0;
+//gap between the HUD and the edges of the window
+const EDGE: f32 = 12.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeterKind {
+    //fills back up after a dash
+    Dash,
+    Stamina,
+}
+
+//--components--//
+
+//a bar on the HUD, this is the part that fills up inside its background
+#[derive(Component)]
+pub struct Meter {
+    kind: MeterKind,
+    //slot of the player it's showing
+    player: usize,
+}
+
+//the whole HUD, a UI node covering the window so everything in it is laid out against the window's edges
+#[derive(Component)]
+pub struct Hud;
+
+//hearts left for the player it's on
+#[derive(Component, Clone, Copy)]
+pub struct PlayerHP(pub i16);
 
 impl Default for PlayerHP {
     fn default() -> Self {
@@ -11,28 +57,504 @@ impl Default for PlayerHP {
     }
 }
 
+//one of a player's hearts, slid across behind its frame to show whichever frame its animation is on
+#[derive(Component)]
+pub struct Heart {
+    player: usize,
+    index: i16,
+}
+
+//the name of the attack a player has
+#[derive(Component)]
+pub struct AttackLabel(usize);
+
+//the icon of the attack a player has, keeps the file it's showing
+#[derive(Component)]
+pub struct AttackIcon {
+    player: usize,
+    shown: String,
+}
+
+//the run-wide numbers along the top, the clock keeps the second it's showing
+#[derive(Component)]
+pub enum HudText {
+    Score,
+    Combo,
+    Wave,
+    Time(u32),
+}
+
+//--resources--//
+
+//kills chained together, only for show so it runs on frame time rather than in the fixed step
+pub struct Combo {
+    count: u32,
+    last_score: u32,
+    left: f32,
+}
+
+impl Combo {
+    pub fn new(score: u32) -> Combo {
+        Combo {
+            count: 0,
+            last_score: score,
+            left: 0.0,
+        }
+    }
+
+    //catch up with the score, returns whether what's shown needs to change
+    fn update(&mut self, score: u32, delta: f32) -> bool {
+        let mut changed = false;
+        if score > self.last_score {
+            self.count += score - self.last_score;
+            self.left = COMBO_TIME;
+            changed = true;
+        } else if score < self.last_score {
+            //the run was put back, by a reconcile
+            self.count = 0;
+            changed = true;
+        }
+        self.last_score = score;
+
+        if self.count > 0 {
+            self.left -= delta;
+            if self.left <= 0.0 {
+                self.count = 0;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+//--spawning--//
+
+//build the HUD for a run, the first player's side is the top left, the second's the top right
+//and the score, wave and bombs sit in the middle
+pub fn spawn_hud(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    heart: &Handle<Image>,
+    attack_icon: &Handle<Image>,
+    hearts: &[PlayerHP],
+    max_bombs: u32,
+) {
+    let text = |size: f32, color: Color| TextStyle {
+        font: font.clone(),
+        font_size: size,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(Hud)
+        .with_children(|hud| {
+            for (slot, hp) in hearts.iter().enumerate() {
+                //ui y runs upwards, so bottom is the distance from the top of the window
+                let (position, align) = if slot == 0 {
+                    (
+                        Rect {
+                            left: Val::Px(EDGE),
+                            bottom: Val::Px(EDGE),
+                            ..Default::default()
+                        },
+                        AlignItems::FlexStart,
+                    )
+                } else {
+                    (
+                        Rect {
+                            right: Val::Px(EDGE),
+                            bottom: Val::Px(EDGE),
+                            ..Default::default()
+                        },
+                        AlignItems::FlexEnd,
+                    )
+                };
+                hud.spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position,
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: align,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|panel| {
+                    panel.spawn_bundle(row()).with_children(|row| {
+                        for index in 0..PlayerHP::default().0 {
+                            spawn_heart(row, heart, slot, index, index < hp.0);
+                        }
+                    });
+                    spawn_meter(panel, MeterKind::Stamina, slot, Vec2::new(150.0, 10.0));
+                    spawn_meter(panel, MeterKind::Dash, slot, Vec2::new(104.0, 6.0));
+                    panel.spawn_bundle(row()).with_children(|row| {
+                        row.spawn_bundle(ImageBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(24.0), Val::Px(24.0)),
+                                margin: Rect {
+                                    right: Val::Px(6.0),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            image: attack_icon.clone().into(),
+                            ..Default::default()
+                        })
+                        .insert(AttackIcon {
+                            player: slot,
+                            shown: String::new(),
+                        });
+                        row.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                text(16.0, Color::WHITE),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(AttackLabel(slot));
+                    });
+                });
+            }
+
+            hud.spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Px(0.0),
+                        right: Val::Px(0.0),
+                        bottom: Val::Px(EDGE),
+                        ..Default::default()
+                    },
+                    flex_direction: FlexDirection::ColumnReverse,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                color: Color::NONE.into(),
+                ..Default::default()
+            })
+            .with_children(|panel| {
+                panel.spawn_bundle(row()).with_children(|row| {
+                    row.spawn_bundle(TextBundle {
+                        text: Text::with_section("", text(24.0, Color::WHITE), Default::default()),
+                        ..Default::default()
+                    })
+                    .insert(HudText::Score);
+                    row.spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect {
+                                left: Val::Px(10.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        text: Text::with_section("", text(20.0, COMBO_COLOR), Default::default()),
+                        ..Default::default()
+                    })
+                    .insert(HudText::Combo);
+                });
+                panel.spawn_bundle(row()).with_children(|row| {
+                    row.spawn_bundle(TextBundle {
+                        text: Text::with_section("", text(16.0, Color::WHITE), Default::default()),
+                        ..Default::default()
+                    })
+                    .insert(HudText::Wave);
+                    row.spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect {
+                                left: Val::Px(12.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        text: Text::with_section("", text(16.0, Color::WHITE), Default::default()),
+                        ..Default::default()
+                    })
+                    .insert(HudText::Time(u32::MAX));
+                });
+                bomb::spawn_icons(panel, max_bombs);
+            });
+        });
+}
+
+//a line of HUD items left to right, a little apart from the next line
+pub fn row() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            align_items: AlignItems::Center,
+            margin: Rect {
+                top: Val::Px(3.0),
+                bottom: Val::Px(3.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    }
+}
+
+//the frame only shows one heart's worth of the image, the image moves left to show the empty one
+fn spawn_heart(
+    row: &mut ChildBuilder,
+    heart: &Handle<Image>,
+    player: usize,
+    index: i16,
+    full: bool,
+) {
+    row.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(HEART_SIZE), Val::Px(HEART_SIZE)),
+            margin: Rect {
+                right: Val::Px(4.0),
+                ..Default::default()
+            },
+            overflow: Overflow::Hidden,
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    })
+    .with_children(|frame| {
+        frame
+            .spawn_bundle(ImageBundle {
+                style: Style {
+                    size: Size::new(Val::Px(HEART_SIZE * 2.0), Val::Px(HEART_SIZE)),
+                    position_type: PositionType::Absolute,
+                    position: heart_offset(if full { 0 } else { 1 }),
+                    ..Default::default()
+                },
+                image: heart.clone().into(),
+                ..Default::default()
+            })
+            .insert(animation::heart(full))
+            .insert(Heart { player, index });
+    });
+}
+
+fn heart_offset(frame: usize) -> Rect<Val> {
+    Rect {
+        left: Val::Px(-(frame as f32) * HEART_SIZE),
+        bottom: Val::Px(0.0),
+        ..Default::default()
+    }
+}
+
+fn spawn_meter(panel: &mut ChildBuilder, kind: MeterKind, player: usize, size: Vec2) {
+    panel
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+                margin: Rect {
+                    top: Val::Px(3.0),
+                    bottom: Val::Px(3.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: METER_BACK.into(),
+            ..Default::default()
+        })
+        .with_children(|back| {
+            back.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    ..Default::default()
+                },
+                color: FULL_COLOR.into(),
+                ..Default::default()
+            })
+            .insert(Meter { kind, player });
+        });
+}
+
+//the bit of an attack's path that says what it is, patterns/split.pattern.ron is split
+fn attack_label(name: &str) -> &str {
+    let file = name.rsplit('/').next().unwrap_or(name);
+    file.split('.').next().unwrap_or(file)
+}
+
+//--systems--//
+
+//take a heart off each player hit this step, it's part of the fixed step since enemies only go after
+//players who are still alive
 pub fn player_hit_handler(
     mut events: EventReader<PlayerHitEvent>,
-    mut hp: Local<PlayerHP>,
-    sheets: Res<Assets<TextureAtlas>>,
-    mut q: Query<(&mut TextureAtlasSprite, &Handle<TextureAtlas>, &Index)>,
+    mut players: Query<(&Player, &mut PlayerHP)>,
+    mut sounds: EventWriter<SoundEvent>,
 ) {
-    for _ev in events.iter() {
+    for ev in events.iter() {
+        let (player, mut hp) = match players.get_mut(ev.0) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
         hp.0 -= 1;
-        info!("Player HP is {}", hp.0);
+        sounds.send(SoundEvent::PlayerHit);
+        info!("Player {} HP is {}", player.slot + 1, hp.0);
 
         if hp.0 == 0 {
-            info!("Player died");
+            info!("Player {} died", player.slot + 1);
         } else if hp.0 < 0 {
             error!("This shouldn't happen in the real game");
-            return;
         }
+    }
+}
+
+//break or refill hearts whenever a player's HP moves, from a hit or a reconcile
+pub fn show_hearts(
+    players: Query<(&Player, &PlayerHP), Changed<PlayerHP>>,
+    mut hearts: Query<(&Heart, &mut Animation)>,
+) {
+    for (player, hp) in players.iter() {
+        for (heart, mut animation) in hearts.iter_mut() {
+            if heart.player != player.slot {
+                continue;
+            }
+            if heart.index < hp.0 {
+                animation.play("full");
+            } else if animation.current() == "full" {
+                animation.play("break");
+            }
+        }
+    }
+}
 
-        for (mut heart_sprite, handle, i) in q.iter_mut() {
-            if i.0 == hp.0 as i32 {
-                let atlas = sheets.get(handle).unwrap();
-                heart_sprite.index = (heart_sprite.index + 1) % atlas.textures.len();
+//a heart stays empty once it's done breaking
+pub fn settle_hearts(
+    mut events: EventReader<AnimationFinished>,
+    mut q: Query<&mut Animation, With<Heart>>,
+) {
+    for ev in events.iter() {
+        if ev.clip != "break" {
+            continue;
+        }
+        if let Ok(mut heart) = q.get_mut(ev.entity) {
+            heart.play("empty");
+        }
+    }
+}
+
+//slide each heart's image to the frame its animation is on
+pub fn show_heart_frames(mut q: Query<(&Animation, &mut Style), With<Heart>>) {
+    for (animation, mut style) in q.iter_mut() {
+        let position = heart_offset(animation.frame());
+        if style.position != position {
+            style.position = position;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_meters(
+    config: Res<GameConfig>,
+    players: Query<(&Player, &Dash, &Stamina), Or<(Changed<Dash>, Changed<Stamina>)>>,
+    mut q: Query<(&Meter, &mut Style, &mut UiColor)>,
+) {
+    for (player, dash, stamina) in players.iter() {
+        for (meter, mut style, mut color) in q.iter_mut() {
+            if meter.player != player.slot {
+                continue;
+            }
+            let (fill, fill_color) = match meter.kind {
+                MeterKind::Dash => {
+                    let charge = dash.charge(&config);
+                    (charge, if charge >= 1.0 { FULL_COLOR } else { LOW_COLOR })
+                }
+                MeterKind::Stamina => {
+                    let fill_color = if stamina.current >= config.dash_stamina {
+                        STAMINA_COLOR
+                    } else {
+                        LOW_COLOR
+                    };
+                    (stamina.current / stamina.max, fill_color)
+                }
+            };
+            //most steps nothing moves, and a write marks the node for layout again
+            let width = Val::Percent(fill.clamp(0.0, 1.0) * 100.0);
+            if style.size.width != width {
+                style.size.width = width;
+            }
+            if color.0 != fill_color {
+                color.0 = fill_color;
+            }
+        }
+    }
+}
+
+//name and icon of each player's attack, the icon is only known once the attack's pattern is in
+pub fn show_attacks(
+    asset_server: Res<AssetServer>,
+    manifest: Res<AssetManifest>,
+    patterns: Res<Assets<BulletPattern>>,
+    players: Query<(&Player, &CurrentAttack, ChangeTrackers<CurrentAttack>)>,
+    mut labels: Query<(&AttackLabel, &mut Text)>,
+    mut icons: Query<(&mut AttackIcon, &mut UiImage)>,
+) {
+    for (player, attack, tracker) in players.iter() {
+        if tracker.is_changed() {
+            for (label, mut text) in labels.iter_mut() {
+                if label.0 != player.slot {
+                    continue;
+                }
+                if let Some(section) = text.sections.first_mut() {
+                    section.value = attack_label(attack.0.name()).to_string();
+                }
+            }
+        }
+
+        let file = attack
+            .0
+            .icon(&patterns)
+            .unwrap_or_else(|| manifest.path("fireball"));
+        for (mut icon, mut image) in icons.iter_mut() {
+            if icon.player != player.slot || icon.shown == file {
+                continue;
+            }
+            image.0 = asset_server.load(file);
+            icon.shown = file.to_string();
+        }
+    }
+}
+
+//score, combo, wave and run time, each only rewritten when what it shows has changed
+pub fn update_run_text(
+    time: Res<Time>,
+    score: Res<Score>,
+    wave: Res<Wave>,
+    run_time: Res<RunTime>,
+    mut combo: ResMut<Combo>,
+    mut q: Query<(&mut HudText, &mut Text)>,
+) {
+    let combo_changed = combo.update(score.0, time.delta_seconds());
+    let seconds = run_time.0 as u32;
+
+    for (mut kind, mut text) in q.iter_mut() {
+        let value = match *kind {
+            HudText::Score if score.is_changed() => format!("Score {}", score.0),
+            HudText::Combo if combo_changed => {
+                if combo.count > 1 {
+                    format!("x{}", combo.count)
+                } else {
+                    String::new()
+                }
+            }
+            HudText::Wave if wave.is_changed() => format!("Wave {}", wave.0 + 1),
+            HudText::Time(shown) if shown != seconds => {
+                *kind = HudText::Time(seconds);
+                format!("{}:{:02}", seconds / 60, seconds % 60)
             }
+            _ => continue,
+        };
+        if let Some(section) = text.sections.first_mut() {
+            section.value = value;
         }
     }
 }